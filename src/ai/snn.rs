@@ -3,6 +3,7 @@ use serde::{Serialize, Deserialize};
 use fixed::types::I48F16; // Số học dấu phẩy tĩnh 48.16 bit
 use rayon::prelude::*;    // Xử lý song song
 use parking_lot::RwLock;  // Mutex nhanh
+use sha2::{Sha256, Digest};
 use std::sync::Arc;
 
 // Định nghĩa kiểu số Deterministic (Bất biến trên mọi máy)
@@ -17,19 +18,50 @@ pub struct Neuron {
     pub last_spike_height: u64,
 }
 
+/// Tham số khởi tạo mạng SNN (khai báo trong chain spec)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SnnParams {
+    pub layer_sizes: Vec<usize>, // [input, hidden..., output]
+    pub threshold: f64,
+    pub decay: f64,
+    pub initial_weight: f64,
+}
+
+impl Default for SnnParams {
+    fn default() -> Self {
+        // Cấu trúc mạng: Input(64) -> Hidden(128) -> Output(10)
+        Self { layer_sizes: vec![64, 128, 10], threshold: 1.0, decay: 0.9, initial_weight: 0.01 }
+    }
+}
+
 impl Neuron {
     pub fn new(input_size: usize) -> Self {
-        let default_weight = DNum::from_num(0.01); 
+        Self::with_params(input_size, &SnnParams::default())
+    }
+
+    pub fn with_params(input_size: usize, params: &SnnParams) -> Self {
+        // f64 -> DNum làm tròn xác định, mọi node cho cùng một giá trị bit
+        let default_weight = DNum::from_num(params.initial_weight);
         Self {
             potential: DNum::from_num(0),
-            threshold: DNum::from_num(1.0),
-            decay: DNum::from_num(0.9), 
+            threshold: DNum::from_num(params.threshold),
+            decay: DNum::from_num(params.decay),
             weights: vec![default_weight; input_size],
             last_spike_height: 0,
         }
     }
 }
 
+/// Phần thay đổi theo block của một nơ-ron; threshold, decay, weights cố định theo chain spec
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NeuronState {
+    pub potential: DNum,
+    pub last_spike_height: u64,
+}
+
+/// Trạng thái SNN đồng thuận lưu theo từng block: NeuronState của từng lớp
+pub type SnnState = Vec<Vec<NeuronState>>;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Layer {
     pub neurons: Vec<Neuron>,
@@ -41,6 +73,11 @@ impl Layer {
         Self { neurons }
     }
 
+    pub fn with_params(size: usize, input_size: usize, params: &SnnParams) -> Self {
+        let neurons = (0..size).map(|_| Neuron::with_params(input_size, params)).collect();
+        Self { neurons }
+    }
+
     pub fn forward(&mut self, inputs: &[DNum], current_height: u64) -> Vec<DNum> {
         // Rayon par_iter_mut() chia việc cho CPU
         self.neurons.par_iter_mut().map(|n| {
@@ -70,6 +107,13 @@ pub struct SNN {
     pub layers: Arc<RwLock<Vec<Layer>>>,
 }
 
+/// Kết quả Proof of Intelligence cho một block
+pub struct SpikeProof {
+    pub snn_root: String,  // Hash trạng thái SNN TRƯỚC khi kích thích
+    pub score: DNum,
+    pub state: SnnState, // Trạng thái SNN SAU khi kích thích
+}
+
 impl SNN {
     pub fn new() -> Self {
         // Cấu trúc mạng: Input(64) -> Hidden(128) -> Output(10)
@@ -91,4 +135,78 @@ impl SNN {
         
         current_input
     }
+
+    pub fn from_params(params: &SnnParams) -> Self {
+        let layers = params.layer_sizes.windows(2)
+            .map(|w| Layer::with_params(w[1], w[0], params))
+            .collect();
+        Self::from_layers(layers)
+    }
+
+    pub fn from_layers(layers: Vec<Layer>) -> Self {
+        Self { layers: Arc::new(RwLock::new(layers)) }
+    }
+
+    /// Phần trạng thái thay đổi sau mỗi lần kích thích (potential, lần spike cuối)
+    pub fn state(&self) -> SnnState {
+        self.layers.read().iter()
+            .map(|layer| layer.neurons.iter()
+                .map(|n| NeuronState { potential: n.potential, last_spike_height: n.last_spike_height })
+                .collect())
+            .collect()
+    }
+
+    /// Dựng mạng từ tham số chain spec rồi nạp `state`. None nếu state không khớp kích thước mạng.
+    pub fn restore(params: &SnnParams, state: &SnnState) -> Option<Self> {
+        let snn = Self::from_params(params);
+        {
+            let mut layers = snn.layers.write();
+            if layers.len() != state.len() {
+                return None;
+            }
+            for (layer, layer_state) in layers.iter_mut().zip(state) {
+                if layer.neurons.len() != layer_state.len() {
+                    return None;
+                }
+                for (neuron, neuron_state) in layer.neurons.iter_mut().zip(layer_state) {
+                    neuron.potential = neuron_state.potential;
+                    neuron.last_spike_height = neuron_state.last_spike_height;
+                }
+            }
+        }
+        Some(snn)
+    }
+
+    /// Cam kết trạng thái: SHA256 trên bit thô (fixed-point) của weights & potentials
+    pub fn state_hash(&self) -> String {
+        let layers = self.layers.read();
+        let mut hasher = Sha256::new();
+        for layer in layers.iter() {
+            hasher.update((layer.neurons.len() as u64).to_le_bytes());
+            for n in &layer.neurons {
+                hasher.update(n.potential.to_bits().to_le_bytes());
+                hasher.update(n.threshold.to_bits().to_le_bytes());
+                hasher.update(n.decay.to_bits().to_le_bytes());
+                hasher.update(n.last_spike_height.to_le_bytes());
+                hasher.update((n.weights.len() as u64).to_le_bytes());
+                for w in &n.weights {
+                    hasher.update(w.to_bits().to_le_bytes());
+                }
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Proof of Intelligence: kích thích mạng bằng input cố định tại `height`.
+    /// Chỉ dùng DNum nên mọi node tái tạo được cùng một kết quả.
+    pub fn prove(&self, height: u64) -> SpikeProof {
+        let snn_root = self.state_hash();
+        let input_size = self.layers.read().first()
+            .and_then(|l| l.neurons.first())
+            .map_or(0, |n| n.weights.len());
+        let inputs = vec![DNum::from_num(0.5); input_size];
+        let score: DNum = self.process(inputs, height).iter().sum();
+
+        SpikeProof { snn_root, score, state: self.state() }
+    }
 }
@@ -1,24 +1,29 @@
 // src/ai/snn_core.rs
 use tokio::sync::RwLock;
 use std::sync::Arc;
-use crate::core::storage::Storage;
+use crate::core::storage::ChainStore;
 use crate::ai::cache::SmartCache;
 use crate::ai::tools::{Oracle, LLMBridge};
 use crate::ethics::EthicsFilter;
-use crate::ai::snn::{SNN, DNum}; // [FIX] Import Deterministic SNN
-use fixed::types::I48F16;
+use crate::ai::snn::{SNN, DNum, SnnParams}; // [FIX] Import Deterministic SNN
 
-pub struct SNNCore {
+/// Mạng SNN đồng thuận sau block `hash`: weights dựng từ chain spec, potential lấy từ Storage
+pub fn consensus_snn<S: ChainStore>(storage: &S, params: &SnnParams, hash: &str) -> Option<SNN> {
+    storage.get_snn_state(hash).and_then(|state| SNN::restore(params, &state))
+}
+
+pub struct SNNCore<S: ChainStore> {
     // Thay thế Vec<BioNeuron> bằng SNN struct chuẩn
+    // Mạng này chỉ phục vụ chat/dreaming; mạng đồng thuận được dựng lại từ Storage
     network: Arc<SNN>, 
-    storage: Arc<Storage>,
+    storage: Arc<S>,
     oracle: Oracle,
     llm: LLMBridge,
     cache: SmartCache,
 }
 
-impl SNNCore {
-    pub fn new(storage: Arc<Storage>, cache: SmartCache) -> Self {
+impl<S: ChainStore> SNNCore<S> {
+    pub fn new(storage: Arc<S>, cache: SmartCache) -> Self {
         println!("🧠 SNN CORE: INITIALIZED (Deterministic Mode)");
         Self {
             network: Arc::new(SNN::new()), // Khởi tạo mạng nơ-ron chuẩn
@@ -29,7 +34,12 @@ impl SNNCore {
         }
     }
 
-    /// Tính toán điểm Spike Score (Consensus Critical)
+    /// Mạng SNN đồng thuận tại block `hash` (dùng khi đào block kế tiếp)
+    pub fn consensus_at(&self, params: &SnnParams, hash: &str) -> Option<SNN> {
+        consensus_snn(&*self.storage, params, hash)
+    }
+
+    /// Tính toán điểm Spike Score cho chat/dreaming (không dùng cho đồng thuận)
     pub async fn forward(&self, intensity: f32) -> f32 {
         // [FIX] Chuyển đổi f32 sang DNum (Fixed Point)
         let input_val = DNum::from_num(intensity);
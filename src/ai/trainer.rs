@@ -1,12 +1,13 @@
 // src/ai/trainer.rs
 use std::sync::Arc;
 use crate::ai::snn_core::SNNCore;
+use crate::core::storage::ChainStore;
 use tokio::time::{sleep, Duration};
 
 pub struct AutoTrainer;
 
 impl AutoTrainer {
-    pub async fn start(snn: Arc<SNNCore>) {
+    pub async fn start<S: ChainStore>(snn: Arc<SNNCore<S>>) {
         println!("🏋️ AUTO TRAINER: STARTED (STDP Protocol Active)");
         
         loop {
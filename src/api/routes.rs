@@ -3,20 +3,38 @@ use actix_web::{web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use crate::core::chain::PappapChain;
-use crate::core::transaction::{Mempool, Transaction};
+use crate::core::block::Block;
+use crate::core::transaction::{Mempool, MempoolStats, Transaction, TxError};
 use crate::core::governance::NeuroDAO;
+use crate::core::storage::ChainStore;
+use crate::core::merkle::{merkle_proof, verify_proof, MerkleProof};
 use crate::ai::snn_core::SNNCore;
 use crate::network::webnode::WebNodeManager;
+use crate::network::peers::PeerBook;
+
+const MAX_HEADERS_PER_REQUEST: u64 = 500;
+const MAX_BLOCKS_PER_REQUEST: u64 = 100;
+const MAX_ACCOUNT_TXS: usize = 100;
 
 // --- DTOs (Data Transfer Objects) ---
 
 #[derive(Serialize)]
 struct StatusResponse {
     node_version: String,
+    chain_id: String, // Client phải đưa chain_id này vào payload ký giao dịch
     height: u64,
     last_hash: String,
     peers: usize,
     mempool_size: usize,
+    mempool: MempoolStats,
+}
+
+#[derive(Serialize)]
+struct TxSubmitResponse {
+    accepted: bool,
+    tx_id: String,
+    code: Option<&'static str>,   // Mã lỗi ổn định khi bị từ chối
+    reason: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -31,53 +49,303 @@ struct ChatResponse {
     answer: String,
 }
 
+#[derive(Serialize)]
+struct TxProofResponse {
+    block_height: u64,
+    block_hash: String,
+    tx_root: String,
+    proof: MerkleProof,
+}
+
 #[derive(Deserialize)]
-struct ProposalRequest {
-    title: String,
-    description: String,
+struct BlocksQuery {
+    from: Option<u64>,  // Mặc định: trang mới nhất
+    limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct BlocksPage {
+    height: u64,
+    blocks: Vec<Block>,
+    next_from: Option<u64>, // Con trỏ trang kế tiếp (None = hết)
+}
+
+#[derive(Serialize)]
+struct TxLookupResponse {
+    status: &'static str, // "pending" | "confirmed"
+    transaction: Transaction,
+    block_height: Option<u64>,
+    block_hash: Option<String>,
+    position: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct AccountQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct AccountTx {
+    block_height: u64,
+    transaction: Transaction,
+}
+
+#[derive(Serialize)]
+struct AccountResponse {
+    address: String,
+    balance: u64,
+    nonce: u64,
+    pending: Vec<Transaction>,
+    transactions: Vec<AccountTx>, // Mới nhất trước
+}
+
+#[derive(Deserialize)]
+struct HeadersQuery {
+    from: u64,
+    count: Option<u64>,
+}
+
+/// Thân block đã bị xóa bởi chế độ pruning (header vẫn còn)
+#[derive(Serialize)]
+struct PrunedResponse {
+    error: &'static str, // Luôn là "block_pruned"
+    height: u64,
+    pruned_below: u64, // Node chỉ còn thân block từ độ cao này trở lên
+}
+
+#[derive(Deserialize)]
+struct VerifyProofRequest {
+    block_height: u64,
+    proof: MerkleProof,
+}
+
+/// 410 Gone nếu block `height` đã bị prune, để client phân biệt với block không tồn tại
+fn pruned<S: ChainStore>(storage: &S, height: u64) -> Option<HttpResponse> {
+    storage.is_pruned(height).then(|| HttpResponse::Gone().json(PrunedResponse {
+        error: "block_pruned",
+        height,
+        pruned_below: storage.get_pruned_height(),
+    }))
 }
 
 // --- HANDLERS ---
 
 /// GET /status - Kiểm tra trạng thái Node
-async fn get_node_status(
-    chain: web::Data<Arc<PappapChain>>,
-    mempool: web::Data<Arc<Mempool>>,
-    // Lưu ý: peer_count cần được inject từ main.rs nếu muốn hiển thị
+async fn get_node_status<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    mempool: web::Data<Arc<Mempool<S>>>,
+    peers: web::Data<Arc<PeerBook>>,
 ) -> impl Responder {
     let height = chain.storage.get_height();
     let last_hash = chain.storage.get_last_hash();
     
     HttpResponse::Ok().json(StatusResponse {
         node_version: "0.8.1".to_string(),
+        chain_id: chain.spec.chain_id.clone(),
         height,
         last_hash,
-        peers: 0, // Cần inject peer_count atomic để lấy số thực
+        peers: peers.list().iter().filter(|p| p.connected).count(), // Peer đang kết nối
         mempool_size: mempool.size(),
+        mempool: mempool.stats(),
     })
 }
 
 /// POST /tx - Gửi giao dịch mới
-async fn submit_transaction(
-    mempool: web::Data<Arc<Mempool>>,
+async fn submit_transaction<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    mempool: web::Data<Arc<Mempool<S>>>,
     tx: web::Json<Transaction>,
 ) -> impl Responder {
-    // 1. Validate Transaction (Signature, Format)
-    if !tx.verify() {
-        return HttpResponse::BadRequest().body("Invalid Signature");
+    // Mempool kiểm tra toàn bộ: id, mã hóa, chữ ký, chain id, số dư & nonce
+    let tx = tx.into_inner();
+    let tx_id = tx.id.clone();
+    match mempool.add_tx(tx.clone()) {
+        Ok(()) => {
+            // Gossip cho peer để node đào kế tiếp cũng có giao dịch
+            chain.broadcast_tx(&tx);
+            HttpResponse::Ok().json(TxSubmitResponse { accepted: true, tx_id, code: None, reason: None })
+        }
+        Err(e) => {
+            let body = TxSubmitResponse { accepted: false, tx_id, code: Some(e.code()), reason: Some(e.to_string()) };
+            match e {
+                TxError::Duplicate(_) => HttpResponse::Conflict().json(body),
+                TxError::PoolFull => HttpResponse::ServiceUnavailable().json(body),
+                _ => HttpResponse::BadRequest().json(body),
+            }
+        }
+    }
+}
+
+/// GET /headers?from=&count= - Đồng bộ header không kèm thân block
+async fn get_headers<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    query: web::Query<HeadersQuery>,
+) -> impl Responder {
+    let count = query.count.unwrap_or(MAX_HEADERS_PER_REQUEST).min(MAX_HEADERS_PER_REQUEST);
+    HttpResponse::Ok().json(chain.storage.get_headers(query.from, count))
+}
+
+/// GET /blocks?from=&limit= - Danh sách block của chuỗi chính theo trang
+async fn list_blocks<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    query: web::Query<BlocksQuery>,
+) -> impl Responder {
+    let height = chain.storage.get_height();
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_BLOCKS_PER_REQUEST);
+    let from = query.from.unwrap_or_else(|| (height + 1).saturating_sub(limit));
+    if let Some(gone) = pruned(&*chain.storage, from) {
+        return gone;
+    }
+    let blocks = chain.storage.get_blocks(from, limit);
+    let next = from + blocks.len() as u64;
+
+    HttpResponse::Ok().json(BlocksPage {
+        height,
+        next_from: if next <= height { Some(next) } else { None },
+        blocks,
+    })
+}
+
+/// GET /blocks/{height} - Block trên chuỗi chính theo chiều cao
+async fn get_block_by_height<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    path: web::Path<u64>,
+) -> impl Responder {
+    let height = path.into_inner();
+    match chain.storage.get_block(height) {
+        Some(block) => HttpResponse::Ok().json(block),
+        None => pruned(&*chain.storage, height)
+            .unwrap_or_else(|| HttpResponse::NotFound().body("Block not found")),
     }
+}
 
-    // 2. Add to Mempool
-    if mempool.add_tx(tx.into_inner()) {
-        HttpResponse::Ok().body("Transaction Accepted")
-    } else {
-        HttpResponse::Conflict().body("Transaction already exists or invalid")
+/// GET /blocks/hash/{hash} - Block theo hash (kể cả block nhánh phụ)
+async fn get_block_by_hash<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let hash = path.into_inner();
+    match chain.storage.get_block_by_hash(&hash) {
+        Some(block) => HttpResponse::Ok().json(block),
+        // Header còn mà thân mất: block đã bị prune
+        None => chain.storage.get_header(&hash)
+            .and_then(|header| pruned(&*chain.storage, header.index))
+            .unwrap_or_else(|| HttpResponse::NotFound().body("Block not found")),
     }
 }
 
+/// GET /tx/{id} - Giao dịch đang chờ trong Mempool hoặc đã nằm trong block
+async fn get_transaction<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    mempool: web::Data<Arc<Mempool<S>>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if let Some((block, position)) = chain.storage.find_transaction(&id) {
+        return HttpResponse::Ok().json(TxLookupResponse {
+            status: "confirmed",
+            transaction: block.transactions[position].clone(),
+            block_height: Some(block.header.index),
+            block_hash: Some(block.hash),
+            position: Some(position),
+        });
+    }
+    match mempool.get(&id) {
+        Some(tx) => HttpResponse::Ok().json(TxLookupResponse {
+            status: "pending",
+            transaction: tx,
+            block_height: None,
+            block_hash: None,
+            position: None,
+        }),
+        None => HttpResponse::NotFound().body("Transaction not found"),
+    }
+}
+
+/// GET /accounts/{address}?limit= - Số dư, nonce và lịch sử giao dịch
+async fn get_account<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    mempool: web::Data<Arc<Mempool<S>>>,
+    path: web::Path<String>,
+    query: web::Query<AccountQuery>,
+) -> impl Responder {
+    let address = path.into_inner();
+    let account = chain.storage.get_account(&address).unwrap_or_default();
+    let limit = query.limit.unwrap_or(MAX_ACCOUNT_TXS).min(MAX_ACCOUNT_TXS);
+    let transactions = chain.storage.get_address_history(&address, limit)
+        .into_iter()
+        .map(|(block_height, transaction)| AccountTx { block_height, transaction })
+        .collect();
+
+    HttpResponse::Ok().json(AccountResponse {
+        balance: account.balance,
+        nonce: account.nonce,
+        pending: mempool.pending_for(&address),
+        transactions,
+        address,
+    })
+}
+
+/// GET /blocks/{height}/proof/{tx_id} - Bằng chứng Merkle cho giao dịch trong block
+async fn get_tx_proof<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    path: web::Path<(u64, String)>,
+) -> impl Responder {
+    let (height, tx_id) = path.into_inner();
+    let Some(block) = chain.storage.get_block(height) else {
+        return pruned(&*chain.storage, height)
+            .unwrap_or_else(|| HttpResponse::NotFound().body("Block not found"));
+    };
+
+    let tx_hashes = block.tx_hashes();
+    let Some(index) = tx_hashes.iter().position(|h| *h == tx_id) else {
+        return HttpResponse::NotFound().body("Transaction not in block");
+    };
+
+    match merkle_proof(&tx_hashes, index) {
+        Some(proof) => HttpResponse::Ok().json(TxProofResponse {
+            block_height: block.header.index,
+            tx_root: block.header.tx_root,
+            block_hash: block.hash,
+            proof,
+        }),
+        None => HttpResponse::InternalServerError().body("Failed to build proof"),
+    }
+}
+
+/// POST /proof/verify - Kiểm tra bằng chứng Merkle với tx_root của block trên chuỗi chính
+async fn verify_tx_proof<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    req: web::Json<VerifyProofRequest>,
+) -> impl Responder {
+    // Chỉ cần header nên vẫn kiểm tra được với block đã bị prune
+    let header = chain.storage.get_canonical_hash(req.block_height)
+        .and_then(|hash| chain.storage.get_header(&hash));
+    let Some(header) = header else {
+        return HttpResponse::NotFound().body("Block not found");
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "valid": verify_proof(&req.proof, &header.tx_root),
+        "tx_root": header.tx_root,
+    }))
+}
+
+/// GET /supply - Tổng cung và thưởng block kế tiếp
+async fn get_supply<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+) -> impl Responder {
+    let height = chain.storage.get_height();
+    HttpResponse::Ok().json(serde_json::json!({
+        "height": height,
+        "total_supply": chain.storage.get_total_supply(),
+        "next_block_reward": chain.spec.emission.reward_at(height + 1),
+    }))
+}
+
 /// POST /ai/chat - Trò chuyện với Pappap AI
-async fn ask_ai(
-    snn: web::Data<Arc<SNNCore>>,
+async fn ask_ai<S: ChainStore>(
+    snn: web::Data<Arc<SNNCore<S>>>,
     req: web::Json<ChatRequest>,
 ) -> impl Responder {
     let (score, src, ans) = snn.process_text(&req.prompt).await;
@@ -89,21 +357,46 @@ async fn ask_ai(
     })
 }
 
-/// GET /governance/proposals - Lấy danh sách đề xuất
-async fn list_proposals(
-    dao: web::Data<Arc<NeuroDAO>>,
+/// GET /governance/proposals - Lấy danh sách đề xuất (tạo & bỏ phiếu qua POST /tx)
+async fn list_proposals<S: ChainStore>(
+    dao: web::Data<Arc<NeuroDAO<S>>>,
+) -> impl Responder {
+    HttpResponse::Ok().json(dao.list_proposals())
+}
+
+/// GET /governance/proposals/{id} - Chi tiết một đề xuất
+async fn get_proposal<S: ChainStore>(
+    dao: web::Data<Arc<NeuroDAO<S>>>,
+    path: web::Path<u64>,
+) -> impl Responder {
+    match dao.get_proposal(path.into_inner()) {
+        Some(p) => HttpResponse::Ok().json(p),
+        None => HttpResponse::NotFound().body("Proposal not found"),
+    }
+}
+
+/// GET /facts/{key} - Đọc kho tri thức on-chain
+async fn get_fact<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
+    path: web::Path<String>,
 ) -> impl Responder {
-    let props = dao.list_proposals().await;
-    HttpResponse::Ok().json(props)
+    match chain.storage.recall_fact(&path.into_inner()) {
+        Some(fact) => HttpResponse::Ok().json(fact),
+        None => HttpResponse::NotFound().body("Fact not found"),
+    }
 }
 
-/// POST /governance/proposals - Tạo đề xuất mới
-async fn create_proposal(
-    dao: web::Data<Arc<NeuroDAO>>,
-    req: web::Json<ProposalRequest>,
+/// GET /snapshot - Snapshot trạng thái mới nhất (nguồn cho fast sync)
+async fn get_snapshot<S: ChainStore>(
+    chain: web::Data<Arc<PappapChain<S>>>,
 ) -> impl Responder {
-    let id = dao.create_proposal(req.title.clone(), req.description.clone()).await;
-    HttpResponse::Ok().json(serde_json::json!({ "id": id, "status": "Created" }))
+    match chain.storage.get_snapshot() {
+        // Snapshot của nhánh đã bị reorg khỏi chuỗi chính thì không phục vụ nữa
+        Some(snapshot) if chain.storage.get_canonical_hash(snapshot.height()).as_deref() == Some(snapshot.block.hash.as_str()) => {
+            HttpResponse::Ok().json(snapshot)
+        }
+        _ => HttpResponse::NotFound().body("No snapshot available"),
+    }
 }
 
 /// GET /webnodes - Lấy thống kê Web Workers
@@ -117,16 +410,72 @@ async fn get_webnodes(
     }))
 }
 
+/// GET /peers - Peer đang kết nối hoặc có vết: điểm gossipsub, số message sai, hạn ban
+async fn list_peers(
+    peers: web::Data<Arc<PeerBook>>,
+) -> impl Responder {
+    HttpResponse::Ok().json(peers.list())
+}
+
 // --- CONFIGURATOR ---
 
-pub fn config(cfg: &mut web::ServiceConfig) {
+pub fn config<S: ChainStore>(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/v1")
-            .route("/status", web::get().to(get_node_status))
-            .route("/tx", web::post().to(submit_transaction))
-            .route("/ai/chat", web::post().to(ask_ai))
-            .route("/governance/proposals", web::get().to(list_proposals))
-            .route("/governance/proposals", web::post().to(create_proposal))
+            .route("/status", web::get().to(get_node_status::<S>))
+            .route("/tx", web::post().to(submit_transaction::<S>))
+            .route("/supply", web::get().to(get_supply::<S>))
+            .route("/tx/{id}", web::get().to(get_transaction::<S>))
+            .route("/accounts/{address}", web::get().to(get_account::<S>))
+            .route("/headers", web::get().to(get_headers::<S>))
+            .route("/snapshot", web::get().to(get_snapshot::<S>))
+            .route("/blocks", web::get().to(list_blocks::<S>))
+            .route("/blocks/hash/{hash}", web::get().to(get_block_by_hash::<S>))
+            .route("/blocks/{height}", web::get().to(get_block_by_height::<S>))
+            .route("/blocks/{height}/proof/{tx_id}", web::get().to(get_tx_proof::<S>))
+            .route("/proof/verify", web::post().to(verify_tx_proof::<S>))
+            .route("/ai/chat", web::post().to(ask_ai::<S>))
+            .route("/governance/proposals", web::get().to(list_proposals::<S>))
+            .route("/governance/proposals/{id}", web::get().to(get_proposal::<S>))
+            .route("/facts/{key}", web::get().to(get_fact::<S>))
             .route("/webnodes", web::get().to(get_webnodes))
+            .route("/peers", web::get().to(list_peers))
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::chain::tests::{test_node, MINER_A};
+    use crate::core::memory_store::MemoryStore;
+    use crate::core::transaction::tests::{test_key, test_spec};
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_web::test]
+    async fn pruned_blocks_keep_headers_and_answer_gone() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let node = test_node(&spec, MINER_A).await;
+        let mut hashes = Vec::new();
+        for _ in 0..4 {
+            hashes.push(node.mine_block(true).await.unwrap().hash);
+        }
+        assert_eq!(node.storage.prune_blocks(3).unwrap(), 2);
+
+        // Header còn (fork choice, sync), thân block #1..#2 đã bị xóa
+        for hash in &hashes[..2] {
+            assert!(node.storage.get_header(hash).is_some());
+            assert!(node.storage.get_block_by_hash(hash).is_none());
+        }
+        assert!(node.storage.get_block_by_hash(&hashes[2]).is_some());
+
+        let app = test::init_service(
+            App::new().app_data(web::Data::new(Arc::new(node))).configure(config::<MemoryStore>)
+        ).await;
+        let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&app, get("/api/v1/blocks/1")).await.status(), StatusCode::GONE);
+        let uri = format!("/api/v1/blocks/hash/{}", hashes[1]);
+        assert_eq!(test::call_service(&app, get(&uri)).await.status(), StatusCode::GONE);
+        assert_eq!(test::call_service(&app, get("/api/v1/blocks/3")).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, get("/api/v1/blocks/9")).await.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,221 @@
+// src/config.rs
+// Cấu hình riêng của từng node (khác với chain spec dùng chung cho cả mạng)
+use crate::constants::{
+    DEFAULT_API_BIND, DEFAULT_DATA_DIR, DEFAULT_LISTEN_ADDR, DEFAULT_SNAPSHOT_INTERVAL, MIN_PRUNE_KEEP_BLOCKS,
+    NODE_KEY_FILE_NAME,
+};
+use crate::core::wallet::is_valid_address;
+use libp2p::Multiaddr;
+use serde::{Serialize, Deserialize};
+use std::path::PathBuf;
+
+/// Backend lưu trữ dữ liệu chuỗi
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Sled,
+    Memory, // Chỉ nằm trong RAM, mất khi tắt node
+}
+
+impl StorageBackend {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "sled" => Ok(StorageBackend::Sled),
+            "memory" => Ok(StorageBackend::Memory),
+            other => Err(format!("Unknown storage backend '{}' (expected sled or memory)", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NodeConfig {
+    /// Địa chỉ nhận thưởng block & phí giao dịch
+    #[serde(default)]
+    pub miner_address: Option<String>,
+    #[serde(default)]
+    pub storage: StorageBackend,
+    /// Thư mục DB sled (mặc định: DEFAULT_DATA_DIR)
+    #[serde(default)]
+    pub data_dir: Option<String>,
+    /// Chụp snapshot trạng thái mỗi N block (mặc định: DEFAULT_SNAPSHOT_INTERVAL, 0 = tắt)
+    #[serde(default)]
+    pub snapshot_interval: Option<u64>,
+    /// Fast sync qua P2P khi DB còn trống (mặc định: tắt)
+    #[serde(default)]
+    pub fast_sync: Option<bool>,
+    /// Pruning: chỉ giữ thân của N block gần nhất (None = giữ toàn bộ)
+    #[serde(default)]
+    pub prune_keep_blocks: Option<u64>,
+    /// Multiaddr P2P lắng nghe (mặc định: DEFAULT_LISTEN_ADDR)
+    #[serde(default)]
+    pub listen_addrs: Vec<String>,
+    /// Peer kết nối khi khởi động và kết nối lại khi mất, vd. "/ip4/10.0.0.2/tcp/9000/p2p/12D3KooW..."
+    #[serde(default)]
+    pub bootstrap_peers: Vec<String>,
+    /// Tìm peer trong mạng LAN qua mDNS (mặc định: bật)
+    #[serde(default)]
+    pub mdns: Option<bool>,
+    /// File khóa libp2p của node, giữ PeerId cố định qua các lần khởi động
+    /// (mặc định: <data_dir>/node.key với backend sled; backend memory dùng khóa tạm)
+    #[serde(default)]
+    pub node_key_file: Option<String>,
+    /// Địa chỉ API HTTP (mặc định: DEFAULT_API_BIND)
+    #[serde(default)]
+    pub api_bind: Option<String>,
+}
+
+/// Cấu hình mạng P2P đã kiểm tra, truyền cho P2PNode
+#[derive(Clone, Debug)]
+pub struct NetworkConfig {
+    pub listen_addrs: Vec<Multiaddr>,
+    pub bootstrap_peers: Vec<Multiaddr>,
+    pub mdns: bool,
+}
+
+/// Danh sách phân tách bằng dấu phẩy trong biến môi trường
+fn split_list(raw: &str) -> Vec<String> {
+    raw.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect()
+}
+
+fn parse_multiaddrs(field: &str, raw: &[String]) -> Result<Vec<Multiaddr>, String> {
+    raw.iter()
+        .map(|addr| addr.parse().map_err(|e| format!("Invalid {} entry '{}': {}", field, addr, e)))
+        .collect()
+}
+
+impl NodeConfig {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read node config {}: {}", path, e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("Invalid node config {}: {}", path, e))
+    }
+
+    /// Đọc file từ NODE_CONFIG (nếu có); MINER_ADDRESS, STORAGE_BACKEND, DATA_DIR,
+    /// SNAPSHOT_INTERVAL, FAST_SYNC, PRUNE_KEEP_BLOCKS, LISTEN_ADDRS, BOOTSTRAP_PEERS
+    /// (danh sách phân tách bằng dấu phẩy), MDNS, NODE_KEY_FILE, API_BIND ghi đè giá trị trong file
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = match std::env::var("NODE_CONFIG") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path)?,
+            _ => Self::default(),
+        };
+        if let Ok(address) = std::env::var("MINER_ADDRESS") {
+            if !address.is_empty() {
+                config.miner_address = Some(address);
+            }
+        }
+        if let Ok(backend) = std::env::var("STORAGE_BACKEND") {
+            if !backend.is_empty() {
+                config.storage = StorageBackend::parse(&backend)?;
+            }
+        }
+        if let Ok(dir) = std::env::var("DATA_DIR") {
+            if !dir.is_empty() {
+                config.data_dir = Some(dir);
+            }
+        }
+        if let Ok(interval) = std::env::var("SNAPSHOT_INTERVAL") {
+            if !interval.is_empty() {
+                let interval = interval.parse()
+                    .map_err(|_| format!("Invalid SNAPSHOT_INTERVAL: {}", interval))?;
+                config.snapshot_interval = Some(interval);
+            }
+        }
+        if let Ok(fast_sync) = std::env::var("FAST_SYNC") {
+            if !fast_sync.is_empty() {
+                let fast_sync = fast_sync.parse()
+                    .map_err(|_| format!("Invalid FAST_SYNC: {} (expected true or false)", fast_sync))?;
+                config.fast_sync = Some(fast_sync);
+            }
+        }
+        if let Ok(keep) = std::env::var("PRUNE_KEEP_BLOCKS") {
+            if !keep.is_empty() {
+                let keep = keep.parse()
+                    .map_err(|_| format!("Invalid PRUNE_KEEP_BLOCKS: {}", keep))?;
+                config.prune_keep_blocks = Some(keep);
+            }
+        }
+        if let Ok(addrs) = std::env::var("LISTEN_ADDRS") {
+            if !addrs.is_empty() {
+                config.listen_addrs = split_list(&addrs);
+            }
+        }
+        if let Ok(peers) = std::env::var("BOOTSTRAP_PEERS") {
+            if !peers.is_empty() {
+                config.bootstrap_peers = split_list(&peers);
+            }
+        }
+        if let Ok(mdns) = std::env::var("MDNS") {
+            if !mdns.is_empty() {
+                let mdns = mdns.parse().map_err(|_| format!("Invalid MDNS: {} (expected true or false)", mdns))?;
+                config.mdns = Some(mdns);
+            }
+        }
+        if let Ok(path) = std::env::var("NODE_KEY_FILE") {
+            if !path.is_empty() {
+                config.node_key_file = Some(path);
+            }
+        }
+        if let Ok(bind) = std::env::var("API_BIND") {
+            if !bind.is_empty() {
+                config.api_bind = Some(bind);
+            }
+        }
+        config.network()?;
+        if let Some(keep) = config.prune_keep_blocks {
+            if keep < MIN_PRUNE_KEEP_BLOCKS {
+                return Err(format!("prune_keep_blocks must be at least {} (got {})", MIN_PRUNE_KEEP_BLOCKS, keep));
+            }
+        }
+        if let Some(address) = &config.miner_address {
+            if !is_valid_address(address) {
+                return Err(format!("Invalid miner address: {}", address));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn data_dir(&self) -> &str {
+        self.data_dir.as_deref().unwrap_or(DEFAULT_DATA_DIR)
+    }
+
+    pub fn snapshot_interval(&self) -> u64 {
+        self.snapshot_interval.unwrap_or(DEFAULT_SNAPSHOT_INTERVAL)
+    }
+
+    pub fn fast_sync(&self) -> bool {
+        self.fast_sync.unwrap_or(false)
+    }
+
+    pub fn node_key_file(&self) -> Option<PathBuf> {
+        match (&self.node_key_file, self.storage) {
+            (Some(path), _) => Some(PathBuf::from(path)),
+            (None, StorageBackend::Sled) => Some(PathBuf::from(self.data_dir()).join(NODE_KEY_FILE_NAME)),
+            (None, StorageBackend::Memory) => None,
+        }
+    }
+
+    pub fn api_bind(&self) -> &str {
+        self.api_bind.as_deref().unwrap_or(DEFAULT_API_BIND)
+    }
+
+    pub fn network(&self) -> Result<NetworkConfig, String> {
+        let mut listen_addrs = parse_multiaddrs("listen_addrs", &self.listen_addrs)?;
+        if listen_addrs.is_empty() {
+            listen_addrs.push(DEFAULT_LISTEN_ADDR.parse().map_err(|e| format!("Invalid default listen address: {}", e))?);
+        }
+        Ok(NetworkConfig {
+            listen_addrs,
+            bootstrap_peers: parse_multiaddrs("bootstrap_peers", &self.bootstrap_peers)?,
+            mdns: self.mdns.unwrap_or(true),
+        })
+    }
+
+    /// Địa chỉ miner bắt buộc phải cấu hình: không tự tạo ví tạm (phần thưởng đổi địa chỉ mỗi lần khởi động)
+    pub fn miner_address(&self) -> Result<String, String> {
+        self.miner_address.clone().ok_or_else(|| {
+            "No miner address configured. Set MINER_ADDRESS or miner_address in NODE_CONFIG \
+             (`pappap-ai-chain new-wallet <file>` creates a wallet)".to_string()
+        })
+    }
+}
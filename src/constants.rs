@@ -13,3 +13,78 @@ pub const FORBIDDEN_GENES: [u64; 7] = [7, 77, 777, 7777, 77777, 777777, 7777777]
 // Thời gian sống của Ghost Cell: 7 năm (tính bằng giây)
 // 7 * 365 * 24 * 60 * 60 = 220,752,000 giây
 pub const GHOST_CELL_DEATH: u64 = 220_752_000;
+
+// Độ lệch thời gian tối đa cho phép của Block so với đồng hồ local (giây)
+pub const MAX_BLOCK_FUTURE_DRIFT: u64 = 15;
+
+// Phiên bản định dạng BlockHeader
+pub const BLOCK_VERSION: u32 = 1;
+
+// Số bucket của cam kết trạng thái: mỗi block chỉ băm lại các bucket có key bị thay đổi
+pub const STATE_BUCKETS: usize = 4096;
+
+// Tiền tố miền ký giao dịch (đổi version khi đổi định dạng payload)
+pub const TX_SIGNING_DOMAIN: &str = "PAPPAP-TX-V2";
+
+// Mempool: số giao dịch tối đa, tuổi thọ (giây), mức tăng phí tối thiểu khi thay thế (%)
+pub const MEMPOOL_MAX_TXS: usize = 5_000;
+pub const MEMPOOL_TX_TTL_SECS: u64 = 3 * 60 * 60;
+pub const MEMPOOL_RBF_BUMP_PERCENT: u64 = 10;
+
+// Giới hạn payload của các loại giao dịch (byte)
+pub const MAX_PROPOSAL_TITLE_LEN: usize = 128;
+pub const MAX_PROPOSAL_DESCRIPTION_LEN: usize = 4_096;
+pub const MAX_FACT_KEY_LEN: usize = 256;
+pub const MAX_FACT_VALUE_LEN: usize = 4_096;
+
+// DAO: số phiếu để chốt một đề xuất
+pub const PROPOSAL_VOTE_QUORUM: u64 = 10;
+
+// Thư mục DB sled mặc định (ghi đè bằng NodeConfig.data_dir / DATA_DIR)
+pub const DEFAULT_DATA_DIR: &str = "pappap_v1.db";
+// Tên file khóa libp2p trong thư mục dữ liệu (ghi đè bằng NodeConfig.node_key_file / NODE_KEY_FILE)
+pub const NODE_KEY_FILE_NAME: &str = "node.key";
+// Tác giả hiển thị cho fact nhập lại từ DB v0 (v0 không ghi người tạo)
+pub const LEGACY_FACT_AUTHOR: &str = "legacy-v0";
+
+// Chụp snapshot trạng thái mỗi N block (ghi đè bằng NodeConfig.snapshot_interval, 0 = tắt)
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1_000;
+
+// Pruning: số thân block tối thiểu phải giữ (đủ cho reorg), chu kỳ quét (giây), số block xóa mỗi đợt
+pub const MIN_PRUNE_KEEP_BLOCKS: u64 = 64;
+pub const PRUNE_INTERVAL_SECS: u64 = 10;
+pub const PRUNE_BATCH_BLOCKS: u64 = 1_000;
+
+// Chain sync (P2P): chu kỳ dò peer (giây), hạn chờ một request (giây),
+// số header / block tối đa trong một response, kích thước tối đa một message (byte)
+pub const SYNC_INTERVAL_SECS: u64 = 5;
+pub const SYNC_REQUEST_TIMEOUT_SECS: u64 = 30;
+pub const SYNC_MAX_HEADERS: u64 = 500;
+pub const SYNC_MAX_BLOCKS: usize = 64;
+pub const SYNC_MAX_MESSAGE_BYTES: u32 = 32 * 1024 * 1024;
+
+// Fast sync: số peer tối thiểu cùng xác nhận header của snapshot, thời gian chờ đủ peer (giây)
+pub const FAST_SYNC_MIN_PEERS: usize = 2;
+pub const FAST_SYNC_WAIT_SECS: u64 = 60;
+
+// Kích thước tối đa một message gossip (byte): đủ cho block lớn nhất theo block policy mặc định
+pub const MAX_GOSSIP_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+// Chấm điểm peer gossip: mức phạt mỗi message sai (P4 của gossipsub, phạt theo bình phương số message)
+pub const GOSSIP_INVALID_MESSAGE_WEIGHT: f64 = -20.0;
+// Ban peer gửi quá PEER_MAX_STRIKES block/tx sai trong PEER_STRIKE_WINDOW_SECS giây, ban trong PEER_BAN_SECS giây
+pub const PEER_MAX_STRIKES: u32 = 5;
+pub const PEER_STRIKE_WINDOW_SECS: u64 = 600;
+pub const PEER_BAN_SECS: u64 = 3_600;
+// Chu kỳ cập nhật điểm peer & gỡ ban hết hạn (giây)
+pub const PEER_REFRESH_SECS: u64 = 5;
+
+// Địa chỉ mặc định: P2P lắng nghe (ghi đè bằng NodeConfig.listen_addrs / LISTEN_ADDRS), API HTTP (NodeConfig.api_bind / API_BIND)
+pub const DEFAULT_LISTEN_ADDR: &str = "/ip4/0.0.0.0/tcp/9000";
+pub const DEFAULT_API_BIND: &str = "0.0.0.0:8080";
+// Kết nối lại bootstrap peer: chờ từ BOOTSTRAP_RETRY_MIN_SECS, nhân đôi sau mỗi lần lỗi, tối đa BOOTSTRAP_RETRY_MAX_SECS (giây)
+pub const BOOTSTRAP_RETRY_MIN_SECS: u64 = 5;
+pub const BOOTSTRAP_RETRY_MAX_SECS: u64 = 300;
+// Kademlia: chu kỳ random walk (giây); chỉ tự quay số peer tìm qua DHT khi còn dưới KAD_DIAL_MAX_PEERS kết nối
+pub const KAD_WALK_SECS: u64 = 60;
+pub const KAD_DIAL_MAX_PEERS: usize = 25;
@@ -0,0 +1,228 @@
+// src/core/archive.rs
+// Archive di động của chuỗi chính: seed node mới, sao lưu, tái hiện lỗi.
+// Định dạng: MAGIC | bản ghi header | bản ghi block #0, #1, ...
+// Mỗi bản ghi = độ dài u32 big-endian + bincode.
+use crate::core::block::Block;
+use crate::core::chain_spec::ChainSpec;
+use crate::core::storage::{ChainStore, StorageError};
+use crate::core::validation::{extend_head, BlockError};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const ARCHIVE_MAGIC: &[u8; 8] = b"PAPPAPAR";
+const ARCHIVE_VERSION: u32 = 1;
+// Chặn file hỏng khiến node cấp phát bộ nhớ khổng lồ
+const MAX_RECORD_BYTES: u32 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArchiveHeader {
+    pub version: u32,
+    pub chain_id: String,
+    pub genesis_hash: String,
+    pub height: u64, // Block cuối cùng trong archive
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    Format(String),
+    WrongChain { expected: String, found: String },
+    BrokenLink { index: u64, expected: String, found: String }, // prev_hash không khớp block trước trong archive
+    Diverged { index: u64 }, // Chuỗi local đã có block khác ở độ cao này
+    Pruned(u64), // Node đang chạy chế độ pruning, thân block đã bị xóa
+    Block { index: u64, error: BlockError },
+    Storage(StorageError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Io(e) => write!(f, "archive I/O error: {}", e),
+            ArchiveError::Format(e) => write!(f, "malformed archive: {}", e),
+            ArchiveError::WrongChain { expected, found } => write!(f, "archive belongs to chain {}, node runs {}", found, expected),
+            ArchiveError::BrokenLink { index, expected, found } => write!(
+                f, "block #{} links to {} but the previous archived block is {}", index, found, expected
+            ),
+            ArchiveError::Diverged { index } => write!(f, "local chain already has a different block #{}", index),
+            ArchiveError::Pruned(index) => write!(f, "block #{} has been pruned, export needs a full archive node", index),
+            ArchiveError::Block { index, error } => write!(f, "block #{} rejected: {}", index, error),
+            ArchiveError::Storage(e) => write!(f, "storage failure: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<io::Error> for ArchiveError {
+    fn from(e: io::Error) -> Self {
+        ArchiveError::Io(e)
+    }
+}
+
+impl From<StorageError> for ArchiveError {
+    fn from(e: StorageError) -> Self {
+        ArchiveError::Storage(e)
+    }
+}
+
+fn write_record<W: Write, T: Serialize>(writer: &mut W, value: &T) -> Result<(), ArchiveError> {
+    let bytes = bincode::serialize(value).map_err(|e| ArchiveError::Format(e.to_string()))?;
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|len| *len <= MAX_RECORD_BYTES)
+        .ok_or_else(|| ArchiveError::Format(format!("record of {} bytes is too large", bytes.len())))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Đọc bản ghi kế tiếp; None khi gặp EOF đúng ranh giới bản ghi
+fn read_record<R: Read, T: serde::de::DeserializeOwned>(reader: &mut R) -> Result<Option<T>, ArchiveError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_RECORD_BYTES {
+        return Err(ArchiveError::Format(format!("record of {} bytes is too large", len)));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    bincode::deserialize(&bytes).map(Some).map_err(|e| ArchiveError::Format(e.to_string()))
+}
+
+/// Ghi chuỗi chính từ genesis tới `to` (mặc định: đỉnh hiện tại). Trả về header đã ghi.
+pub fn export_chain<S: ChainStore, W: Write>(
+    storage: &S,
+    spec: &ChainSpec,
+    writer: &mut W,
+    to: Option<u64>,
+) -> Result<ArchiveHeader, ArchiveError> {
+    let genesis = storage.get_block(0)
+        .ok_or_else(|| ArchiveError::Format("database has no genesis block".to_string()))?;
+    let height = to.map_or(storage.get_height(), |to| to.min(storage.get_height()));
+    if storage.get_pruned_height() > 1 {
+        return Err(ArchiveError::Pruned(1));
+    }
+    let header = ArchiveHeader {
+        version: ARCHIVE_VERSION,
+        chain_id: spec.chain_id.clone(),
+        genesis_hash: genesis.hash.clone(),
+        height,
+    };
+
+    writer.write_all(ARCHIVE_MAGIC)?;
+    write_record(writer, &header)?;
+    for index in 0..=height {
+        let block = storage.get_block(index)
+            .ok_or_else(|| ArchiveError::Format(format!("canonical block #{} is missing", index)))?;
+        write_record(writer, &block)?;
+    }
+    writer.flush()?;
+    Ok(header)
+}
+
+/// Nạp archive vào DB: mỗi block đi qua toàn bộ pipeline kiểm tra như block nhận từ peer.
+/// Block đã có trên chuỗi chính local được bỏ qua, nên có thể chạy tiếp sau khi bị ngắt.
+/// Dừng sau block `to` (nếu có). Trả về số block đã ghi mới.
+pub fn import_chain<S: ChainStore, R: Read>(
+    storage: &S,
+    spec: &ChainSpec,
+    reader: &mut R,
+    to: Option<u64>,
+) -> Result<u64, ArchiveError> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != ARCHIVE_MAGIC {
+        return Err(ArchiveError::Format("not a PAPPAP archive".to_string()));
+    }
+    let header: ArchiveHeader = read_record(reader)?
+        .ok_or_else(|| ArchiveError::Format("missing archive header".to_string()))?;
+    if header.version != ARCHIVE_VERSION {
+        return Err(ArchiveError::Format(format!("unsupported archive version {}", header.version)));
+    }
+    if header.chain_id != spec.chain_id {
+        return Err(ArchiveError::WrongChain { expected: spec.chain_id.clone(), found: header.chain_id });
+    }
+
+    let last = to.map_or(header.height, |to| to.min(header.height));
+    let mut prev_hash: Option<String> = None;
+    let mut imported = 0;
+
+    for expected_index in 0..=last {
+        let block: Block = read_record(reader)?.ok_or_else(|| ArchiveError::Format(format!(
+            "archive ends at block #{}, header promises #{}", expected_index.saturating_sub(1), header.height
+        )))?;
+        let index = block.header.index;
+        if index != expected_index {
+            return Err(ArchiveError::Format(format!("expected block #{}, found #{}", expected_index, index)));
+        }
+
+        // Chuỗi hash trong archive phải liền mạch (genesis phải khớp header)
+        match &prev_hash {
+            None if block.hash != header.genesis_hash => {
+                return Err(ArchiveError::Format(format!("genesis {} does not match header", block.hash)));
+            }
+            Some(prev) if block.header.prev_hash != *prev => {
+                return Err(ArchiveError::BrokenLink { index, expected: prev.clone(), found: block.header.prev_hash });
+            }
+            _ => {}
+        }
+        prev_hash = Some(block.hash.clone());
+
+        // Đã có trên chuỗi chính local (genesis từ chain spec hoặc lần import trước)
+        match storage.get_canonical_hash(index) {
+            Some(local) if local == block.hash => continue,
+            Some(_) => return Err(ArchiveError::Diverged { index }),
+            None => {}
+        }
+
+        extend_head(&block, storage, spec).map_err(|error| ArchiveError::Block { index, error })?;
+        imported += 1;
+
+        if imported % 1000 == 0 {
+            println!("📦 Imported block #{}", index);
+        }
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::chain::tests::{test_node, MINER_A};
+    use crate::core::chain_spec::ensure_genesis;
+    use crate::core::memory_store::MemoryStore;
+    use crate::core::transaction::tests::{test_key, test_spec, transfer};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn export_then_import_into_memory_store_reaches_same_head() {
+        let alice = test_key(1);
+        let spec = Arc::new(test_spec(&[&alice], 1_000_000));
+        let node = test_node(&spec, MINER_A).await;
+        node.mempool.add_tx(transfer(&alice, 1_000, 10, 0)).unwrap();
+        node.mine_block(false).await.unwrap();
+        node.mine_block(true).await.unwrap();
+
+        let mut archive = Vec::new();
+        let header = export_chain(&*node.storage, &spec, &mut archive, None).unwrap();
+        assert_eq!(header.height, 2);
+
+        let fresh = MemoryStore::default();
+        ensure_genesis(&fresh, &spec).unwrap();
+        assert_eq!(import_chain(&fresh, &spec, &mut archive.as_slice(), None).unwrap(), 2);
+        assert_eq!(fresh.get_last_hash(), node.storage.get_last_hash());
+        assert_eq!(fresh.get_state_entries(), node.storage.get_state_entries());
+        // Nạp lại lần nữa: block đã có được bỏ qua
+        assert_eq!(import_chain(&fresh, &spec, &mut archive.as_slice(), None).unwrap(), 0);
+
+        let other = test_spec(&[&alice], 1_000_000);
+        let other = ChainSpec { chain_id: "pappap-other".to_string(), ..other };
+        let result = import_chain(&MemoryStore::default(), &other, &mut archive.as_slice(), None);
+        assert!(matches!(result, Err(ArchiveError::WrongChain { .. })));
+    }
+}
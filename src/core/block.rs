@@ -1,19 +1,44 @@
 // src/core/block.rs
-use crate::constants::{ETERNAL_SIGNATURE, FORBIDDEN_GENES};
+use crate::constants::{BLOCK_VERSION, ETERNAL_SIGNATURE};
 use crate::core::transaction::Transaction;
+use crate::core::merkle::merkle_root;
+use crate::ai::snn::{DNum, SpikeProof};
 use serde::{Serialize, Deserialize};
 use sha2::{Sha256, Digest};
-use std::fmt;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Block {
+/// Phần đầu của Block: đủ để xác minh chuỗi hash mà không cần thân block.
+/// Hash của header (bincode chuẩn) chính là id của Block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockHeader {
+    pub version: u32,
     pub index: u64,
     pub timestamp: u64,
     pub prev_hash: String,
+    pub tx_root: String,     // Merkle root của hash giao dịch
+    pub state_root: String,  // Root trạng thái tài khoản SAU block
+    pub snn_root: String,    // Cam kết trạng thái SNN dùng để tính spike_score
+    pub spike_score: DNum,   // Điểm số trí tuệ từ AI (Proof of Intelligence), fixed-point
+    pub miner: String,
+}
+
+impl BlockHeader {
+    /// Mã hóa nhị phân chuẩn: bincode (little-endian, độ dài cố định)
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("Failed to encode block header")
+    }
+
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.encode());
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Block {
+    pub header: BlockHeader,
     pub hash: String,
     pub transactions: Vec<Transaction>,
-    pub spike_score: f32,       // Điểm số trí tuệ từ AI (Proof of Intelligence)
-    pub miner: String,
     pub eternal_signature: [u8; 7],
     pub forbidden_gene_checked: bool,
 }
@@ -24,54 +49,54 @@ impl Block {
         prev_hash: String,
         transactions: Vec<Transaction>,
         miner: String,
-        spike_score: f32, // Thay vì DNum, ta dùng f32 ở lớp giao tiếp để đơn giản hóa serialize
+        proof: &SpikeProof,
+        state_root: String,
+        forbidden_genes: &[u64], // Danh sách Gene cấm của chain spec
     ) -> Self {
-        let mut block = Self {
+        let header = BlockHeader {
+            version: BLOCK_VERSION,
             index,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
             prev_hash,
+            tx_root: String::new(),
+            state_root,
+            snn_root: proof.snn_root.clone(),
+            spike_score: proof.score,
+            miner,
+        };
+        let mut block = Self {
+            header,
             hash: String::new(),
             transactions,
-            spike_score,
-            miner,
             eternal_signature: ETERNAL_SIGNATURE,
             forbidden_gene_checked: false,
         };
 
         // BẮT BUỘC: Kiểm tra Gene cấm
-        if FORBIDDEN_GENES.contains(&index) {
+        if forbidden_genes.contains(&index) {
             panic!("🚫 BLOCK REJECTED: Forbidden Gene {} Detected.", index);
         }
         block.forbidden_gene_checked = true;
-        
+        block.header.tx_root = block.calculate_tx_root();
+
         // Tính toán Hash sau khi đã điền đầy đủ thông tin
         block.hash = block.calculate_hash();
         block
     }
 
-    pub fn calculate_hash(&self) -> String {
-        // Gom tất cả dữ liệu thành chuỗi để hash
-        let tx_data = self.transactions.iter()
-            .map(|tx| tx.id.clone())
-            .collect::<String>();
-            
-        let input = format!(
-            "{}{}{}{}{}{}{:?}{}",
-            self.index,
-            self.timestamp,
-            self.prev_hash,
-            self.spike_score,
-            self.miner,
-            self.forbidden_gene_checked,
-            self.eternal_signature,
-            tx_data
-        );
+    /// Hash giao dịch được tính lại từ payload, không tin vào `tx.id` do client gửi
+    pub fn tx_hashes(&self) -> Vec<String> {
+        self.transactions.iter().map(|tx| tx.calculate_hash()).collect()
+    }
 
-        let mut hasher = Sha256::new();
-        hasher.update(input);
-        hex::encode(hasher.finalize())
+    pub fn calculate_tx_root(&self) -> String {
+        merkle_root(&self.tx_hashes())
+    }
+
+    pub fn calculate_hash(&self) -> String {
+        self.header.hash()
     }
 }
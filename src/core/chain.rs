@@ -1,80 +1,503 @@
 // src/core/chain.rs
-use crate::constants::FEEDBACK_TIMEOUT_MS;
+use crate::constants::{FEEDBACK_TIMEOUT_MS, PRUNE_BATCH_BLOCKS, PRUNE_INTERVAL_SECS};
 use crate::core::block::Block;
-use crate::core::storage::Storage;
-use crate::core::transaction::Mempool;
+use crate::core::chain_spec::ChainSpec;
+use crate::core::storage::ChainStore;
+use crate::core::transaction::{Mempool, Transaction, TxError, TxKind};
+use crate::core::validation::{execute_block, validate_block, BlockError};
+use crate::core::state::{total_fees, StateOverlay, StateValue, UndoRecorder, NEXT_PROPOSAL_KEY};
+use crate::core::fork_choice::plan_reorg;
+use crate::core::snapshot::StateSnapshot;
+use crate::network::message::{InboundMessage, NetworkMessage, Verdict};
+use libp2p::gossipsub::MessageAcceptance;
+use std::collections::HashSet;
 use crate::ai::snn_core::SNNCore;
 use std::sync::Arc;
-use tokio::time::{sleep, Duration};
-use tokio::sync::mpsc::UnboundedSender;
-
-pub struct PappapChain {
-    pub storage: Arc<Storage>,
-    pub mempool: Arc<Mempool>,
-    pub snn: Arc<SNNCore>,
-    pub p2p_sender: UnboundedSender<Vec<u8>>, // Kênh để bắn Block ra mạng P2P
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+pub struct PappapChain<S: ChainStore> {
+    pub spec: Arc<ChainSpec>,
+    pub storage: Arc<S>,
+    pub mempool: Arc<Mempool<S>>,
+    pub snn: Arc<SNNCore<S>>,
+    pub miner: String, // Địa chỉ nhận thưởng block (từ NodeConfig)
+    pub snapshot_interval: u64, // Chụp snapshot trạng thái mỗi N block (0 = tắt)
+    pub p2p_sender: UnboundedSender<NetworkMessage>, // Kênh để bắn Block / Tx ra mạng P2P
+    // Chỉ một luồng (Miner hoặc Importer) được ghi Block tại một thời điểm
+    commit_lock: Mutex<()>,
 }
 
-impl PappapChain {
+impl<S: ChainStore> PappapChain<S> {
     pub async fn new(
-        storage: Arc<Storage>, 
-        mempool: Arc<Mempool>,
-        snn: Arc<SNNCore>,
-        p2p_sender: UnboundedSender<Vec<u8>>
+        spec: Arc<ChainSpec>,
+        storage: Arc<S>,
+        mempool: Arc<Mempool<S>>,
+        snn: Arc<SNNCore<S>>,
+        miner: String,
+        snapshot_interval: u64,
+        p2p_sender: UnboundedSender<NetworkMessage>
     ) -> Self {
-        Self { storage, mempool, snn, p2p_sender }
+        Self { spec, storage, mempool, snn, miner, snapshot_interval, p2p_sender, commit_lock: Mutex::new(()) }
     }
 
+    /// Vòng lặp sản xuất block theo block policy của chain spec.
+    /// Không polling: Miner ngủ tới hạn block kế tiếp hoặc tới khi Mempool báo có tx mới.
     pub async fn run(&self) {
-        println!("⛏️  MINING ENGINE STARTED: Waiting for transactions...");
-        
+        let policy = self.spec.block_policy.clone();
+        let target = Duration::from_millis(policy.target_block_time_ms);
+        println!("⛏️  MINING ENGINE STARTED: target {}ms | max {} txs / {} bytes | heartbeat: {} | instant seal: {}",
+            policy.target_block_time_ms, policy.max_txs, policy.max_bytes, policy.heartbeat, policy.instant_seal);
+
+        let mut last_block = Instant::now();
         loop {
-            // 1. Kiểm tra Mempool xem có đủ giao dịch để đóng block không
-            // Ở đây demo lấy ít nhất 1 giao dịch, thực tế có thể đào block rỗng
-            if self.mempool.size() == 0 {
-                sleep(Duration::from_millis(1000)).await;
+            // 1. Chờ tới hạn block kế tiếp (instant seal: không chờ)
+            if !policy.instant_seal {
+                sleep_until(last_block + target).await;
+            }
+
+            // 2. Không có tx sẵn sàng (trống hoặc chỉ còn tx sau lỗ nonce):
+            //    đào block rỗng (heartbeat) hoặc ngủ tới khi có tx sẵn sàng
+            let heartbeat = policy.heartbeat && !policy.instant_seal;
+            if !heartbeat && !self.mempool.has_ready() {
+                self.mempool.wait_for_tx().await;
                 continue;
             }
 
-            let txs = self.mempool.pop_n(10); // Lấy tối đa 10 tx
-            println!("⚡ Mining Block with {} transactions...", txs.len());
-
-            // 2. Lấy thông tin Chain hiện tại
-            let height = self.storage.get_height() + 1;
-            let last_hash = self.storage.get_last_hash();
-
-            // 3. AI Consensus (Proof of Intelligence)
-            // AI phải tính toán một giá trị "Spike" dựa trên trạng thái mạng
-            // Đây là bước thay thế Proof of Work (đốt điện)
-            let spike_val = self.snn.forward(0.5).await; // 0.5 là input kích thích
-
-            // 4. Tạo Block mới
-            let new_block = Block::new(
-                height,
-                last_hash,
-                txs,
-                "Local_Miner_01".to_string(), // Tên miner
-                spike_val
-            );
-
-            // 5. Lưu Block vào Storage
-            self.storage.save_block(&new_block);
-            
-            println!("✅ BLOCK #{} MINED | Hash: {} | Spike: {}", 
-                height, 
-                &new_block.hash[0..16], // In ngắn gọn
-                spike_val
-            );
-
-            // 6. Broadcast Block ra mạng P2P
-            if let Ok(block_bytes) = serde_json::to_vec(&new_block) {
-                if let Err(e) = self.p2p_sender.send(block_bytes) {
-                    println!("⚠️ Failed to broadcast block: {}", e);
+            match self.mine_block(heartbeat).await {
+                Some(block) => {
+                    last_block = Instant::now();
+                    self.broadcast(&block);
+                }
+                // Không đào được (Gene cấm, thiếu SNN, tx hết hợp lệ): nghỉ một nhịp
+                None => sleep(Duration::from_millis(FEEDBACK_TIMEOUT_MS)).await,
+            }
+        }
+    }
+
+    /// Đóng gói một block trên đỉnh chuỗi chính. `allow_empty` cho phép block không có giao dịch.
+    pub(crate) async fn mine_block(&self, allow_empty: bool) -> Option<Block> {
+        let policy = &self.spec.block_policy;
+        let txs = self.mempool.select(policy.max_txs, policy.max_bytes);
+        if txs.is_empty() && !allow_empty {
+            return None;
+        }
+        println!("⚡ Mining Block with {} transactions...", txs.len());
+
+        let _guard = self.commit_lock.lock().await;
+
+        // Lấy thông tin Chain hiện tại
+        let height = self.storage.get_height() + 1;
+        let last_hash = self.storage.get_last_hash();
+
+        // Chạm Gene cấm: dừng đào thay vì để Block::new panic
+        if self.spec.is_forbidden_gene(height) {
+            println!("🚫 Forbidden Gene {} reached. Mining halted.", height);
+            return None;
+        }
+
+        // AI Consensus (Proof of Intelligence)
+        // AI phải tính toán một giá trị "Spike" dựa trên trạng thái mạng
+        // Đây là bước thay thế Proof of Work (đốt điện)
+        // Dùng SNN đồng thuận của block cha để mọi validator tái tạo được kết quả
+        let Some(snn) = self.snn.consensus_at(&self.spec.snn, &last_hash) else {
+            println!("❌ Missing SNN state for head {}, cannot mine", last_hash);
+            return None;
+        };
+        let proof = snn.prove(height);
+
+        // Thực thi giao dịch trên trạng thái on-chain, loại bỏ tx không còn hợp lệ
+        let mut overlay = StateOverlay::new(&*self.storage);
+        let mut undo = UndoRecorder::default();
+        let mut included = Vec::with_capacity(txs.len());
+        for tx in txs {
+            match overlay.apply_tx(&tx, &mut undo) {
+                Ok(()) => included.push(tx),
+                Err(e) => {
+                    println!("🗑️ Dropped TX {} from block: {}", tx.id, e);
+                    self.mempool.reject(&tx);
+                }
+            }
+        }
+        if included.is_empty() && !allow_empty {
+            return None;
+        }
+
+        // Coinbase: thưởng theo lịch phát hành + toàn bộ phí cho miner
+        let reward = self.spec.emission.reward_at(height);
+        if let Err(e) = overlay.apply_coinbase(&self.miner, reward, total_fees(&included), &mut undo) {
+            println!("❌ Coinbase rejected, cannot mine: {}", e);
+            return None;
+        }
+
+        let new_block = Block::new(
+            height,
+            last_hash,
+            included,
+            self.miner.clone(),
+            &proof,
+            overlay.state_root(),
+            &self.spec.forbidden_genes,
+        );
+
+        // Lưu Block vào Storage (block + SNN + trạng thái + chỉ mục cùng một giao dịch)
+        if let Err(e) = self.storage.save_block(&new_block, &proof.state, &overlay.into_changes(), &undo.into_log()) {
+            println!("❌ Failed to store mined block #{}: {}", height, e);
+            return None;
+        }
+        self.mempool.remove_included(&new_block.transactions);
+        self.log_proposals(std::slice::from_ref(&new_block));
+        self.maybe_snapshot();
+
+        println!("✅ BLOCK #{} MINED | Hash: {} | Txs: {} | Spike: {}",
+            height,
+            &new_block.hash[0..16], // In ngắn gọn
+            new_block.transactions.len(),
+            proof.score
+        );
+        Some(new_block)
+    }
+
+    /// Log các đề xuất DAO do `blocks` (vừa ghi vào chuỗi chính) tạo ra.
+    /// Id cấp tuần tự nên suy ra được từ bộ đếm sau khi commit; apply_tx không log vì còn chạy khi validate/replay.
+    fn log_proposals(&self, blocks: &[Block]) {
+        let created = blocks.iter()
+            .flat_map(|b| b.transactions.iter())
+            .filter(|tx| matches!(tx.kind, TxKind::Proposal { .. }))
+            .count() as u64;
+        if created == 0 {
+            return;
+        }
+        let Some(StateValue::Counter(next)) = self.storage.get_state(NEXT_PROPOSAL_KEY) else { return };
+        for id in next.saturating_sub(created)..next {
+            println!("📜 Proposal Created: ID {}", id);
+        }
+    }
+
+    /// Chụp snapshot trạng thái khi đỉnh chuỗi chạm bội số của `snapshot_interval`.
+    /// Chỉ gọi khi đang giữ commit_lock để snapshot khớp đúng một block.
+    fn maybe_snapshot(&self) {
+        let height = self.storage.get_height();
+        if self.snapshot_interval == 0 || height == 0 || !height.is_multiple_of(self.snapshot_interval) {
+            return;
+        }
+        let Some(snapshot) = StateSnapshot::capture(&*self.storage) else { return };
+        match self.storage.save_snapshot(&snapshot) {
+            Ok(()) => println!("📸 STATE SNAPSHOT at block #{} ({} entries)", height, snapshot.entries.len()),
+            Err(e) => println!("⚠️ Failed to store snapshot at block #{}: {}", height, e),
+        }
+    }
+
+    /// Broadcast Block ra mạng P2P
+    fn broadcast(&self, block: &Block) {
+        if self.p2p_sender.send(NetworkMessage::Block(block.clone())).is_err() {
+            println!("⚠️ Failed to broadcast block: P2P node is not running");
+        }
+    }
+
+    /// Broadcast giao dịch đã vào Mempool local để node đào kế tiếp cũng nhận được
+    pub fn broadcast_tx(&self, tx: &Transaction) {
+        if self.p2p_sender.send(NetworkMessage::Transaction(tx.clone())).is_err() {
+            println!("⚠️ Failed to broadcast tx {}: P2P node is not running", tx.id);
+        }
+    }
+
+    /// Kiểm tra và ghi một Block nhận từ peer khác.
+    /// Block hợp lệ luôn được lưu; chuỗi chính chỉ đổi khi nhánh của nó nặng hơn.
+    pub async fn import_block(&self, block: Block) -> Result<(), BlockError> {
+        let _guard = self.commit_lock.lock().await;
+
+        let effects = validate_block(&block, &*self.storage, &self.spec)?;
+
+        let head_hash = self.storage.get_last_hash();
+        let head_weight = self.storage.get_weight(&head_hash).unwrap_or_default();
+        let parent_weight = self.storage.get_weight(&block.header.prev_hash).unwrap_or_default();
+        let weight = parent_weight.extend(&block);
+
+        // 1. Nối thẳng vào đỉnh hiện tại
+        if block.header.prev_hash == head_hash {
+            let mut overlay = StateOverlay::new(&*self.storage);
+            let undo = execute_block(&block, &self.spec, &mut overlay)?;
+            self.storage.save_block(&block, &effects.snn_state, &overlay.into_changes(), &undo)?;
+            self.mempool.remove_included(&block.transactions);
+            self.log_proposals(std::slice::from_ref(&block));
+            self.maybe_snapshot();
+            return Ok(());
+        }
+
+        // 2. Nhánh phụ: lưu lại, chưa đủ nặng thì dừng ở đây
+        self.storage.save_side_block(&block, &weight, &effects.snn_state)?;
+        if !weight.is_heavier_than(&head_weight) {
+            println!("🌿 Side-chain Block #{} stored (fork)", block.header.index);
+            return Ok(());
+        }
+
+        // 3. Reorg: gỡ nhánh cũ, gắn nhánh mới
+        let plan = plan_reorg(&*self.storage, &head_hash, &block)
+            .ok_or_else(|| BlockError::UnknownParent(block.header.prev_hash.clone()))?;
+
+        // Rollback trạng thái tài khoản về điểm rẽ rồi thực thi lại nhánh mới.
+        // Nếu nhánh mới vi phạm trạng thái thì giữ nguyên chuỗi chính.
+        let mut overlay = StateOverlay::new(&*self.storage);
+        for b in &plan.retract {
+            let undo = self.storage.get_undo(&b.hash)
+                .ok_or_else(|| BlockError::MissingUndo(b.hash.clone()))?;
+            overlay.revert(&undo);
+        }
+        let mut undos = Vec::with_capacity(plan.enact.len());
+        for b in &plan.enact {
+            undos.push(execute_block(b, &self.spec, &mut overlay)?);
+        }
+        self.storage.apply_reorg(&plan.retract, &plan.enact, &overlay.into_changes(), &undos)?;
+        self.log_proposals(&plan.enact);
+        self.maybe_snapshot();
+
+        // Giao dịch bị bỏ rơi ở nhánh cũ được trả về Mempool
+        let enacted: HashSet<&str> = plan.enact.iter()
+            .flat_map(|b| b.transactions.iter().map(|tx| tx.id.as_str()))
+            .collect();
+        // Trả theo nonce tăng dần để không bị coi là hở nonce
+        let mut orphaned: Vec<&Transaction> = plan.retract.iter()
+            .flat_map(|b| b.transactions.iter())
+            .filter(|tx| !enacted.contains(tx.id.as_str()))
+            .collect();
+        orphaned.sort_by_key(|tx| tx.nonce);
+        let mut returned = 0;
+        for tx in orphaned {
+            if self.mempool.add_tx(tx.clone()).is_ok() {
+                returned += 1;
+            }
+        }
+        for b in &plan.enact {
+            self.mempool.remove_included(&b.transactions);
+        }
+
+        println!("🔀 REORG: -{} / +{} blocks | New head #{} | {} txs returned to mempool",
+            plan.retract.len(), plan.enact.len(), block.header.index, returned);
+        Ok(())
+    }
+
+    /// Chế độ pruning: chỉ giữ thân của `keep` block gần đỉnh nhất.
+    /// Xóa theo từng đợt nhỏ để không giữ commit_lock quá lâu.
+    pub async fn run_pruner(&self, keep: u64) {
+        println!("✂️  PRUNING ENABLED: keeping the last {} block bodies", keep);
+        loop {
+            let pruned = {
+                let _guard = self.commit_lock.lock().await;
+                let target = (self.storage.get_height() + 1).saturating_sub(keep);
+                let below = target.min(self.storage.get_pruned_height().max(1) + PRUNE_BATCH_BLOCKS);
+                match self.storage.prune_blocks(below) {
+                    Ok(count) => count,
+                    Err(e) => {
+                        println!("⚠️ Pruning failed: {}", e);
+                        0
+                    }
                 }
+            };
+            if pruned > 0 {
+                println!("✂️  Pruned {} block bodies (kept from #{})", pruned, self.storage.get_pruned_height());
+                continue;
+            }
+            sleep(Duration::from_secs(PRUNE_INTERVAL_SECS)).await;
+        }
+    }
+
+    /// Vòng lặp nhận message từ P2P: Block đi qua pipeline kiểm tra, giao dịch vào Mempool.
+    /// Kết quả trả về gossipsub qua `verdicts`: Accept thì chuyển tiếp, Reject thì trừ điểm peer,
+    /// Ignore cho lỗi phụ thuộc trạng thái local (trùng, thiếu block cha, hở nonce...).
+    pub async fn run_importer(&self, mut inbound: UnboundedReceiver<InboundMessage>, verdicts: UnboundedSender<Verdict>) {
+        println!("📥 BLOCK IMPORTER STARTED");
+
+        while let Some(InboundMessage { message, id, source }) = inbound.recv().await {
+            let acceptance = match message {
+                NetworkMessage::Block(block) => self.import_gossip_block(block).await,
+                NetworkMessage::Transaction(tx) => self.import_gossip_tx(tx),
+            };
+            let _ = verdicts.send(Verdict { id, source, acceptance });
+        }
+    }
+
+    async fn import_gossip_block(&self, block: Block) -> MessageAcceptance {
+        let (index, hash) = (block.header.index, block.hash.clone());
+        match self.import_block(block).await {
+            Ok(()) => {
+                println!("🧩 BLOCK #{} IMPORTED | Hash: {}", index, &hash[..16.min(hash.len())]);
+                MessageAcceptance::Accept
             }
+            Err(BlockError::AlreadyKnown(_)) => MessageAcceptance::Ignore,
+            Err(e) => {
+                println!("🚫 Gossip Block #{} Rejected: {}", index, e);
+                if e.is_peer_fault() { MessageAcceptance::Reject } else { MessageAcceptance::Ignore }
+            }
+        }
+    }
+
+    fn import_gossip_tx(&self, tx: Transaction) -> MessageAcceptance {
+        let id = tx.id.clone();
+        match self.mempool.add_tx(tx) {
+            Ok(()) => {
+                println!("📨 Gossip TX {} added to mempool", id);
+                MessageAcceptance::Accept
+            }
+            Err(TxError::Duplicate(_)) => MessageAcceptance::Ignore,
+            Err(e) => {
+                println!("🚫 Gossip TX {} Rejected: {}", id, e);
+                if e.is_peer_fault() { MessageAcceptance::Reject } else { MessageAcceptance::Ignore }
+            }
+        }
+    }
+}
 
-            // 7. Nghỉ ngơi theo nhịp sinh học (Feedback Timeout)
-            sleep(Duration::from_millis(FEEDBACK_TIMEOUT_MS)).await;
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ai::cache::SmartCache;
+    use crate::core::chain_spec::ensure_genesis;
+    use crate::core::memory_store::MemoryStore;
+    use crate::core::transaction::tests::{address_of, test_key, test_spec, transfer};
+    use tokio::sync::mpsc::unbounded_channel;
+
+    pub(crate) const MINER_A: &str = "PAPPAP000000000000000000000000000000A1";
+    pub(crate) const MINER_B: &str = "PAPPAP000000000000000000000000000000B2";
+
+    /// Một node dùng MemoryStore, không có P2P (broadcast chỉ in cảnh báo)
+    pub(crate) async fn test_node(spec: &Arc<ChainSpec>, miner: &str) -> PappapChain<MemoryStore> {
+        let storage = Arc::new(MemoryStore::default());
+        ensure_genesis(&*storage, spec).unwrap();
+        let mempool = Arc::new(Mempool::new(storage.clone(), spec.chain_id.clone()));
+        let snn = Arc::new(SNNCore::new(storage.clone(), SmartCache::new()));
+        let (p2p_sender, _) = unbounded_channel();
+        PappapChain::new(spec.clone(), storage, mempool, snn, miner.to_string(), 0, p2p_sender).await
+    }
+
+    fn balance(node: &PappapChain<MemoryStore>, address: &str) -> u64 {
+        node.storage.get_account(address).map_or(0, |account| account.balance)
+    }
+
+    /// Root tính từ digest bucket đã lưu (không có thay đổi chờ) phải khớp header của đỉnh
+    fn assert_state_matches_head(node: &PappapChain<MemoryStore>) {
+        let head = node.storage.get_block_by_hash(&node.storage.get_last_hash()).unwrap();
+        assert_eq!(StateOverlay::new(&*node.storage).state_root(), head.header.state_root);
+    }
+
+    #[tokio::test]
+    async fn reorg_reverts_state_and_returns_orphaned_txs() {
+        let alice = test_key(1);
+        let spec = Arc::new(test_spec(&[&alice], 1_000_000));
+        let (a, b) = (test_node(&spec, MINER_A).await, test_node(&spec, MINER_B).await);
+
+        let tx = transfer(&alice, 1_000, 10, 0);
+        a.mempool.add_tx(tx.clone()).unwrap();
+        let a1 = a.mine_block(false).await.unwrap();
+        assert_eq!(a1.transactions.len(), 1);
+        assert_eq!(balance(&a, &address_of(&alice)), 1_000_000 - 1_010);
+        assert!(a.mempool.get(&tx.id).is_none());
+
+        // Nhánh của B dài hơn: A phải gỡ a1 và theo nhánh B
+        let b1 = b.mine_block(true).await.unwrap();
+        let b2 = b.mine_block(true).await.unwrap();
+        a.import_block(b1).await.unwrap();
+        a.import_block(b2.clone()).await.unwrap();
+
+        assert_eq!(a.storage.get_last_hash(), b2.hash);
+        assert_eq!(a.storage.get_canonical_hash(1), b.storage.get_canonical_hash(1));
+        assert_eq!(balance(&a, &address_of(&alice)), 1_000_000);
+        assert_eq!(a.storage.get_account(&address_of(&alice)).unwrap().nonce, 0);
+        assert_eq!(balance(&a, MINER_A), 0);
+        assert_eq!(balance(&a, MINER_B), balance(&b, MINER_B));
+        assert_eq!(a.storage.get_total_supply(), b.storage.get_total_supply());
+        assert!(a.storage.get_tx_location(&tx.id).is_none());
+        assert!(a.mempool.get(&tx.id).is_some());
+        assert_state_matches_head(&a);
+
+        // a1 vẫn được giữ như block nhánh phụ
+        assert!(a.storage.has_block(&a1.hash));
+    }
+
+    #[tokio::test]
+    async fn two_memory_nodes_converge_after_import_and_reorg() {
+        let alice = test_key(1);
+        let spec = Arc::new(test_spec(&[&alice], 1_000_000));
+        let (a, b) = (test_node(&spec, MINER_A).await, test_node(&spec, MINER_B).await);
+
+        // 1. A đào block có giao dịch, B nhập vào đỉnh của mình
+        let tx0 = transfer(&alice, 500, 10, 0);
+        b.mempool.add_tx(tx0.clone()).unwrap();
+        a.mempool.add_tx(tx0.clone()).unwrap();
+        let a1 = a.mine_block(false).await.unwrap();
+        b.import_block(a1.clone()).await.unwrap();
+        assert_eq!(b.storage.get_last_hash(), a1.hash);
+        assert_eq!(b.storage.get_tx_location(&tx0.id), Some((1, 0)));
+        assert!(b.mempool.get(&tx0.id).is_none());
+        assert_state_matches_head(&b);
+
+        // 2. Mạng bị chia: A đào a2 chứa tx1, B đào hai block rỗng
+        let tx1 = transfer(&alice, 700, 10, 1);
+        a.mempool.add_tx(tx1.clone()).unwrap();
+        let a2 = a.mine_block(false).await.unwrap();
+        let b2 = b.mine_block(true).await.unwrap();
+        let b3 = b.mine_block(true).await.unwrap();
+
+        // 3. Nối lại: a2 nhẹ hơn nên B chỉ lưu làm nhánh phụ, A reorg sang nhánh B
+        b.import_block(a2.clone()).await.unwrap();
+        assert_eq!(b.storage.get_last_hash(), b3.hash);
+        assert!(b.storage.has_block(&a2.hash));
+        a.import_block(b2).await.unwrap();
+        a.import_block(b3.clone()).await.unwrap();
+
+        assert_eq!(a.storage.get_last_hash(), b3.hash);
+        for height in 0..=3 {
+            assert_eq!(a.storage.get_canonical_hash(height), b.storage.get_canonical_hash(height));
         }
+        assert_eq!(a.storage.get_state_entries(), b.storage.get_state_entries());
+        assert_eq!(a.storage.get_state_buckets(), b.storage.get_state_buckets());
+        assert_eq!(a.storage.get_tx_location(&tx0.id), Some((1, 0)));
+        assert!(a.storage.get_tx_location(&tx1.id).is_none());
+        assert!(a.mempool.get(&tx1.id).is_some());
+        assert_state_matches_head(&a);
+    }
+
+    #[tokio::test]
+    async fn reorg_returns_tx_in_front_of_a_still_pooled_nonce() {
+        let alice = test_key(1);
+        let spec = Arc::new(test_spec(&[&alice], 1_000_000));
+        let (a, b) = (test_node(&spec, MINER_A).await, test_node(&spec, MINER_B).await);
+
+        let tx0 = transfer(&alice, 100, 10, 0);
+        a.mempool.add_tx(tx0.clone()).unwrap();
+        a.mine_block(false).await.unwrap();
+        assert_eq!(a.mempool.add_tx(tx0.clone()), Err(TxError::NonceReused { expected: 1, found: 0 }));
+
+        // tx1 vẫn nằm trong Mempool khi block chứa tx0 bị gỡ
+        let tx1 = transfer(&alice, 100, 10, 1);
+        a.mempool.add_tx(tx1.clone()).unwrap();
+        let b1 = b.mine_block(true).await.unwrap();
+        let b2 = b.mine_block(true).await.unwrap();
+        a.import_block(b1).await.unwrap();
+        a.import_block(b2).await.unwrap();
+
+        assert!(a.mempool.has_ready());
+        let selected: Vec<String> = a.mempool.select(10, u64::MAX).into_iter().map(|tx| tx.id).collect();
+        assert_eq!(selected, vec![tx0.id.clone(), tx1.id.clone()]);
+        let a3 = a.mine_block(false).await.unwrap();
+        assert_eq!(a3.transactions.len(), 2);
+        assert_eq!(a.storage.get_account(&address_of(&alice)).unwrap().nonce, 2);
+    }
+
+    #[tokio::test]
+    async fn same_height_fork_switches_only_to_more_spike() {
+        let spec = Arc::new(test_spec(&[], 0));
+        let (a, b) = (test_node(&spec, MINER_A).await, test_node(&spec, MINER_B).await);
+        let a1 = a.mine_block(true).await.unwrap();
+        let b1 = b.mine_block(true).await.unwrap();
+
+        // Cùng độ cao: chỉ đổi đỉnh khi tổng spike của nhánh mới lớn hơn hẳn (bằng nhau giữ a1)
+        let base = a.storage.get_weight(&a1.header.prev_hash).unwrap();
+        let switch = base.extend(&b1).is_heavier_than(&base.extend(&a1));
+        a.import_block(b1.clone()).await.unwrap();
+        let expected = if switch { &b1.hash } else { &a1.hash };
+        assert_eq!(&a.storage.get_last_hash(), expected);
+        assert_state_matches_head(&a);
     }
 }
@@ -0,0 +1,167 @@
+// src/core/chain_spec.rs
+use crate::constants::{BLOCK_VERSION, ETERNAL_SIGNATURE, FORBIDDEN_GENES, GHOST_CELL_DEATH};
+use crate::core::block::{Block, BlockHeader};
+use crate::core::state::{
+    account_key, state_root, Account, StateChanges, StateValue, UndoLog, TOTAL_SUPPLY_KEY,
+};
+use crate::core::storage::ChainStore;
+use crate::ai::snn::{DNum, SnnParams, SNN};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+/// Đặc tả chuỗi: mỗi devnet/testnet có một file riêng, block 0 được dựng hoàn toàn từ đây
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChainSpec {
+    pub chain_id: String,
+    pub genesis_timestamp: u64,
+    #[serde(default)]
+    pub allocations: BTreeMap<String, u64>, // Địa chỉ -> số dư ban đầu
+    pub forbidden_genes: Vec<u64>,
+    pub ghost_cell_lifespan: u64,
+    #[serde(default)]
+    pub snn: SnnParams,
+    #[serde(default)]
+    pub emission: EmissionSchedule,
+    #[serde(default)]
+    pub block_policy: BlockPolicy,
+}
+
+/// Chính sách sản xuất block của mạng. Giới hạn kích thước cũng được validator kiểm tra.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockPolicy {
+    pub target_block_time_ms: u64,
+    pub max_txs: usize,
+    pub max_bytes: u64,          // Tổng kích thước mã hóa của giao dịch trong block
+    #[serde(default)]
+    pub heartbeat: bool,         // Đào block rỗng khi tới hạn mà Mempool trống
+    #[serde(default)]
+    pub instant_seal: bool,      // Devnet: đào ngay khi có tx, bỏ qua target_block_time
+}
+
+impl Default for BlockPolicy {
+    fn default() -> Self {
+        Self {
+            target_block_time_ms: 5_000,
+            max_txs: 1_000,
+            max_bytes: 1_048_576,
+            heartbeat: false,
+            instant_seal: false,
+        }
+    }
+}
+
+/// Lịch phát hành: thưởng block giảm một nửa sau mỗi `halving_interval` block
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EmissionSchedule {
+    pub initial_reward: u64,
+    pub halving_interval: u64, // 0 = không bao giờ giảm
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        Self { initial_reward: 50_000_000, halving_interval: 1_000_000 }
+    }
+}
+
+impl EmissionSchedule {
+    /// Thưởng của block tại `height` (genesis không có thưởng)
+    pub fn reward_at(&self, height: u64) -> u64 {
+        if height == 0 {
+            return 0;
+        }
+        if self.halving_interval == 0 {
+            return self.initial_reward;
+        }
+        let halvings = (height - 1) / self.halving_interval;
+        if halvings >= 64 { 0 } else { self.initial_reward >> halvings }
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            chain_id: "pappap-mainnet".to_string(),
+            genesis_timestamp: 1700000000,
+            allocations: BTreeMap::new(),
+            forbidden_genes: FORBIDDEN_GENES.to_vec(),
+            ghost_cell_lifespan: GHOST_CELL_DEATH,
+            snn: SnnParams::default(),
+            emission: EmissionSchedule::default(),
+            block_policy: BlockPolicy::default(),
+        }
+    }
+}
+
+impl ChainSpec {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read chain spec {}: {}", path, e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("Invalid chain spec {}: {}", path, e))
+    }
+
+    /// Đọc file từ biến môi trường CHAIN_SPEC, không có thì dùng spec mặc định (mainnet)
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("CHAIN_SPEC") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn is_forbidden_gene(&self, index: u64) -> bool {
+        self.forbidden_genes.contains(&index)
+    }
+
+    /// Dựng block 0 một cách tất định: cùng spec -> cùng hash trên mọi node
+    pub fn genesis(&self) -> (Block, SNN, StateChanges) {
+        let snn = SNN::from_params(&self.snn);
+
+        // Trạng thái genesis chỉ gồm allocations, không phụ thuộc nội dung DB
+        let mut state: BTreeMap<String, StateValue> = self.allocations.iter()
+            .map(|(address, balance)| {
+                (account_key(address), StateValue::Account(Account { balance: *balance, nonce: 0 }))
+            })
+            .collect();
+        let supply = self.allocations.values().fold(0u64, |acc, b| acc.saturating_add(*b));
+        state.insert(TOTAL_SUPPLY_KEY.to_string(), StateValue::Counter(supply));
+
+        let header = BlockHeader {
+            version: BLOCK_VERSION,
+            index: 0,
+            timestamp: self.genesis_timestamp,
+            prev_hash: "0".repeat(64),
+            tx_root: "0".repeat(64),
+            state_root: state_root(&state),
+            snn_root: snn.state_hash(),
+            spike_score: DNum::from_num(0),
+            miner: format!("GENESIS:{}", self.chain_id),
+        };
+        let block = Block {
+            hash: header.hash(),
+            header,
+            transactions: Vec::new(),
+            eternal_signature: ETERNAL_SIGNATURE,
+            forbidden_gene_checked: true,
+        };
+        let changes = state.into_iter().map(|(key, value)| (key, Some(value))).collect();
+        (block, snn, changes)
+    }
+}
+
+/// Ghi block 0 nếu Storage còn trống, hoặc xác nhận DB thuộc đúng chuỗi của spec
+pub fn ensure_genesis<S: ChainStore>(storage: &S, spec: &ChainSpec) -> Result<String, String> {
+    let (genesis, snn, changes) = spec.genesis();
+
+    match storage.get_canonical_hash(0) {
+        Some(existing) if existing == genesis.hash => Ok(existing),
+        Some(existing) => Err(format!(
+            "Database genesis {} does not match chain spec '{}' genesis {}",
+            existing, spec.chain_id, genesis.hash
+        )),
+        None => {
+            storage.save_block(&genesis, &snn.state(), &changes, &UndoLog::new())
+                .map_err(|e| format!("Cannot write genesis: {}", e))?;
+            println!("🌱 GENESIS CREATED: {} | Chain: {}", genesis.hash, spec.chain_id);
+            Ok(genesis.hash)
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// src/core/fork_choice.rs
+use crate::core::block::{Block, BlockHeader};
+use crate::core::storage::ChainStore;
+use serde::{Serialize, Deserialize};
+
+/// "Trọng lượng" tích lũy của một nhánh tính đến block có hash tương ứng
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChainWeight {
+    pub height: u64,
+    // Tổng bit thô (DNum) của spike_score từ genesis tới block này: cộng số nguyên, không phụ thuộc làm tròn float
+    pub total_spike: i128,
+}
+
+impl ChainWeight {
+    pub fn extend(&self, block: &Block) -> Self {
+        self.extend_header(&block.header)
+    }
+
+    pub fn extend_header(&self, header: &BlockHeader) -> Self {
+        Self {
+            height: header.index,
+            total_spike: self.total_spike + i128::from(header.spike_score.to_bits()),
+        }
+    }
+
+    /// Luật chọn nhánh: chuỗi dài nhất thắng, hòa thì so tổng spike_score.
+    /// Hoàn toàn bằng nhau -> giữ nhánh đã thấy trước (không reorg).
+    pub fn is_heavier_than(&self, other: &ChainWeight) -> bool {
+        if self.height != other.height {
+            return self.height > other.height;
+        }
+        self.total_spike > other.total_spike
+    }
+}
+
+/// Kế hoạch chuyển nhánh: gỡ `retract` (từ đỉnh cũ đi xuống), rồi gắn `enact` (từ điểm rẽ đi lên)
+pub struct ReorgPlan {
+    pub retract: Vec<Block>,
+    pub enact: Vec<Block>,
+}
+
+/// Tìm điểm rẽ nhánh chung giữa đỉnh hiện tại và `new_tip`.
+/// Trả về None nếu thiếu block trung gian trong Storage.
+pub fn plan_reorg<S: ChainStore>(storage: &S, old_head: &str, new_tip: &Block) -> Option<ReorgPlan> {
+    let mut retract = Vec::new();
+    let mut enact = vec![new_tip.clone()];
+
+    let mut old = storage.get_block_by_hash(old_head);
+    let mut new = storage.get_block_by_hash(&new_tip.header.prev_hash);
+
+    loop {
+        match (old.take(), new.take()) {
+            (Some(o), Some(n)) if o.hash == n.hash => break,
+            (Some(o), Some(n)) => {
+                if o.header.index >= n.header.index {
+                    new = Some(n);
+                    old = storage.get_block_by_hash(&o.header.prev_hash);
+                    retract.push(o);
+                } else {
+                    old = Some(o);
+                    new = storage.get_block_by_hash(&n.header.prev_hash);
+                    enact.push(n);
+                }
+            }
+            // Hai nhánh không có tổ tiên chung (khác genesis)
+            (None, None) => return None,
+            (Some(o), None) => {
+                old = storage.get_block_by_hash(&o.header.prev_hash);
+                retract.push(o);
+            }
+            (None, Some(n)) => {
+                new = storage.get_block_by_hash(&n.header.prev_hash);
+                enact.push(n);
+            }
+        }
+    }
+
+    // Kiểm tra tính liên tục của nhánh mới
+    enact.reverse();
+    let first = enact.first()?;
+    let anchor = retract.last().map(|b| b.header.prev_hash.clone());
+    match anchor {
+        Some(prev) if prev != first.header.prev_hash => None,
+        _ => Some(ReorgPlan { retract, enact }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::snn::DNum;
+    use crate::constants::{BLOCK_VERSION, ETERNAL_SIGNATURE};
+    use crate::core::memory_store::MemoryStore;
+    use crate::core::state::UndoLog;
+
+    fn header(index: u64, prev_hash: &str, score: f64) -> BlockHeader {
+        BlockHeader {
+            version: BLOCK_VERSION,
+            index,
+            timestamp: 1_700_000_000 + index,
+            prev_hash: prev_hash.to_string(),
+            tx_root: "0".repeat(64),
+            state_root: "0".repeat(64),
+            snn_root: "0".repeat(64),
+            spike_score: DNum::from_num(score),
+            miner: format!("miner-{}", score),
+        }
+    }
+
+    fn block(index: u64, prev_hash: &str, score: f64) -> Block {
+        let header = header(index, prev_hash, score);
+        Block {
+            hash: header.hash(),
+            header,
+            transactions: Vec::new(),
+            eternal_signature: ETERNAL_SIGNATURE,
+            forbidden_gene_checked: true,
+        }
+    }
+
+    fn weight(scores: &[f64]) -> ChainWeight {
+        scores.iter().enumerate().fold(ChainWeight::default(), |w, (i, score)| {
+            w.extend_header(&header(i as u64 + 1, "", *score))
+        })
+    }
+
+    #[test]
+    fn longer_chain_wins_regardless_of_spike() {
+        assert!(weight(&[0.0, 0.0, 0.0]).is_heavier_than(&weight(&[100.0, 100.0])));
+        assert!(!weight(&[100.0, 100.0]).is_heavier_than(&weight(&[0.0, 0.0, 0.0])));
+    }
+
+    #[test]
+    fn equal_height_is_decided_by_total_spike() {
+        assert!(weight(&[1.0, 2.5]).is_heavier_than(&weight(&[1.0, 2.25])));
+        assert!(!weight(&[1.0, 2.25]).is_heavier_than(&weight(&[1.0, 2.5])));
+        // Hoàn toàn bằng nhau: không bên nào nặng hơn, giữ nhánh đã thấy trước
+        let (a, b) = (weight(&[1.5, 0.5]), weight(&[0.5, 1.5]));
+        assert_eq!(a, b);
+        assert!(!a.is_heavier_than(&b) && !b.is_heavier_than(&a));
+    }
+
+    #[test]
+    fn total_spike_is_exact_sum_of_raw_bits() {
+        let scores = [0.1, 0.2, 0.3, -0.05, 7.7];
+        let expected: i128 = scores.iter().map(|s| i128::from(DNum::from_num(*s).to_bits())).sum();
+        assert_eq!(weight(&scores).total_spike, expected);
+
+        // Cộng số nguyên nên không phụ thuộc thứ tự (f64 thì có)
+        let mut reversed = scores;
+        reversed.reverse();
+        assert_eq!(weight(&reversed).total_spike, expected);
+    }
+
+    #[test]
+    fn plan_reorg_walks_back_to_the_fork_point() {
+        let storage = MemoryStore::default();
+        let genesis = block(0, &"0".repeat(64), 0.0);
+        let a1 = block(1, &genesis.hash, 1.0);
+        let a2 = block(2, &a1.hash, 1.0);
+        let b1 = block(1, &genesis.hash, 2.0);
+        let b2 = block(2, &b1.hash, 2.0);
+        let b3 = block(3, &b2.hash, 2.0);
+        for b in [&genesis, &a1, &a2] {
+            storage.save_block(b, &Vec::new(), &Default::default(), &UndoLog::new()).unwrap();
+        }
+        for b in [&b1, &b2] {
+            storage.save_side_block(b, &ChainWeight::default(), &Vec::new()).unwrap();
+        }
+
+        let plan = plan_reorg(&storage, &a2.hash, &b3).unwrap();
+        let hashes = |blocks: &[Block]| blocks.iter().map(|b| b.hash.clone()).collect::<Vec<_>>();
+        assert_eq!(hashes(&plan.retract), vec![a2.hash.clone(), a1.hash.clone()]);
+        assert_eq!(hashes(&plan.enact), vec![b1.hash, b2.hash, b3.hash]);
+
+        // Thiếu block trung gian của nhánh mới: không lập được kế hoạch
+        let orphan = block(4, &"f".repeat(64), 2.0);
+        assert!(plan_reorg(&storage, &a2.hash, &orphan).is_none());
+    }
+}
@@ -1,83 +1,81 @@
 // src/core/governance.rs
+// Đề xuất & phiếu bầu là trạng thái on-chain: chỉ thay đổi qua giao dịch
+// TxKind::Proposal / TxKind::Vote đã ký. NeuroDAO chỉ còn là lớp đọc.
+use crate::constants::PROPOSAL_VOTE_QUORUM;
+use crate::core::storage::ChainStore;
+use crate::core::transaction::TxError;
 use serde::{Serialize, Deserialize};
-use tokio::sync::RwLock;
-use std::collections::HashMap;
+use std::sync::Arc;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Proposal {
     pub id: u64,
     pub title: String,
     pub description: String,
+    pub proposer: String, // Địa chỉ người tạo
     pub votes_yes: u64,
     pub votes_no: u64,
     pub status: String, // "Active", "Passed", "Rejected"
 }
 
-pub struct NeuroDAO {
-    proposals: RwLock<HashMap<u64, Proposal>>,
-    next_id: RwLock<u64>,
-}
-
-impl NeuroDAO {
-    pub fn new() -> Self {
-        println!("⚖️  NEURO DAO: GOVERNANCE SYSTEM ONLINE");
+impl Proposal {
+    pub fn new(id: u64, title: String, description: String, proposer: String) -> Self {
         Self {
-            proposals: RwLock::new(HashMap::new()),
-            next_id: RwLock::new(1),
-        }
-    }
-
-    pub async fn create_proposal(&self, title: String, desc: String) -> u64 {
-        let mut id_lock = self.next_id.write().await;
-        let id = *id_lock;
-        *id_lock += 1;
-
-        let prop = Proposal {
             id,
             title,
-            description: desc,
+            description,
+            proposer,
             votes_yes: 0,
             votes_no: 0,
             status: "Active".to_string(),
-        };
+        }
+    }
 
-        self.proposals.write().await.insert(id, prop);
-        println!("📜 Proposal Created: ID {}", id);
-        id
+    pub fn is_active(&self) -> bool {
+        self.status == "Active"
     }
 
-    pub async fn vote(&self, id: u64, approve: bool) -> Result<String, String> {
-        let mut props = self.proposals.write().await;
-        
-        if let Some(p) = props.get_mut(&id) {
-            if p.status != "Active" {
-                return Err("Proposal is closed".to_string());
-            }
-            
-            if approve {
-                p.votes_yes += 1;
-            } else {
-                p.votes_no += 1;
-            }
-            
-            // Logic chốt phiếu đơn giản (Hardcap 10 phiếu)
-            if p.votes_yes + p.votes_no >= 10 {
-                p.status = if p.votes_yes > p.votes_no {
-                    "Passed".to_string()
-                } else {
-                    "Rejected".to_string()
-                };
-                println!("🔨 Proposal {} Closed: {}", id, p.status);
-            }
-            
-            Ok(format!("Voted. Current: {} Yes / {} No", p.votes_yes, p.votes_no))
+    pub fn record_vote(&mut self, approve: bool) -> Result<(), TxError> {
+        if !self.is_active() {
+            return Err(TxError::ProposalClosed(self.id));
+        }
+
+        if approve {
+            self.votes_yes += 1;
         } else {
-            Err("Proposal not found".to_string())
+            self.votes_no += 1;
         }
+
+        // Logic chốt phiếu đơn giản (đủ quorum thì đóng)
+        if self.votes_yes + self.votes_no >= PROPOSAL_VOTE_QUORUM {
+            self.status = if self.votes_yes > self.votes_no {
+                "Passed".to_string()
+            } else {
+                "Rejected".to_string()
+            };
+            println!("🔨 Proposal {} Closed: {}", self.id, self.status);
+        }
+        Ok(())
+    }
+}
+
+pub struct NeuroDAO<S: ChainStore> {
+    storage: Arc<S>,
+}
+
+impl<S: ChainStore> NeuroDAO<S> {
+    pub fn new(storage: Arc<S>) -> Self {
+        println!("⚖️  NEURO DAO: GOVERNANCE SYSTEM ONLINE");
+        Self { storage }
+    }
+
+    pub fn get_proposal(&self, id: u64) -> Option<Proposal> {
+        self.storage.get_proposal(id)
     }
 
-    pub async fn list_proposals(&self) -> Vec<Proposal> {
-        let props = self.proposals.read().await;
-        props.values().cloned().collect()
+    pub fn list_proposals(&self) -> Vec<Proposal> {
+        let mut props = self.storage.get_proposals();
+        props.sort_by_key(|p| p.id);
+        props
     }
 }
@@ -0,0 +1,361 @@
+// src/core/memory_store.rs
+// Backend ChainStore nằm hoàn toàn trong RAM: dùng cho test nhiều node và node thử nghiệm.
+// Mọi lệnh ghi diễn ra dưới một write lock nên nguyên tử như một giao dịch sled.
+use crate::core::block::{Block, BlockHeader};
+use crate::core::snapshot::StateSnapshot;
+use crate::core::fork_choice::ChainWeight;
+use crate::constants::STATE_BUCKETS;
+use crate::core::state::{rehash_buckets, state_bucket, StateChanges, StateValue, UndoLog};
+use crate::core::storage::{indexed_addresses, ChainStore, StorageError};
+use crate::ai::snn::SnnState;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::RwLock;
+
+#[derive(Default)]
+struct MemoryInner {
+    blocks: HashMap<String, Block>, // Mọi block đã biết, kể cả nhánh phụ
+    headers: HashMap<String, BlockHeader>, // Header của mọi block đã biết (kể cả block chỉ có header sau fast sync)
+    weights: HashMap<String, ChainWeight>,
+    snn: HashMap<String, SnnState>,
+    undo: HashMap<String, UndoLog>,
+    snapshot: Option<StateSnapshot>,
+    pruned_below: u64,
+    canonical: BTreeMap<u64, String>, // height -> hash trên chuỗi chính
+    head: Option<(u64, String)>,
+    state: BTreeMap<String, StateValue>,
+    bucket_keys: HashMap<u16, BTreeSet<String>>, // Key trạng thái theo bucket của state_root
+    bucket_digests: HashMap<u16, [u8; 32]>, // Chỉ bucket khác rỗng
+    tx_index: HashMap<String, (u64, u32)>,
+    addr_index: BTreeSet<(String, u64, u32)>,
+}
+
+impl MemoryInner {
+    fn insert_known_block(&mut self, block: &Block, weight: ChainWeight, snn_state: &SnnState) {
+        self.blocks.insert(block.hash.clone(), block.clone());
+        self.headers.insert(block.hash.clone(), block.header.clone());
+        self.weights.insert(block.hash.clone(), weight);
+        self.snn.insert(block.hash.clone(), snn_state.clone());
+    }
+
+    fn apply_state(&mut self, changes: &StateChanges) {
+        let digests = rehash_buckets(
+            changes,
+            |bucket| self.bucket_keys.get(&bucket).map(|keys| keys.iter().cloned().collect()).unwrap_or_default(),
+            |key| self.state.get(key).cloned(),
+        );
+        for (key, value) in changes {
+            let keys = self.bucket_keys.entry(state_bucket(key)).or_default();
+            match value {
+                Some(v) => {
+                    self.state.insert(key.clone(), v.clone());
+                    keys.insert(key.clone());
+                }
+                None => {
+                    self.state.remove(key);
+                    keys.remove(key);
+                }
+            }
+        }
+        for (bucket, digest) in digests {
+            if digest == [0u8; 32] {
+                self.bucket_digests.remove(&bucket);
+            } else {
+                self.bucket_digests.insert(bucket, digest);
+            }
+        }
+    }
+
+    fn set_head(&mut self, block: &Block) {
+        self.head = Some((block.header.index, block.hash.clone()));
+    }
+
+    /// `canonical = true`: thêm chỉ mục cho giao dịch của block, `false`: gỡ bỏ (block bị retract)
+    fn index_block(&mut self, block: &Block, canonical: bool) {
+        let height = block.header.index;
+        for (pos, tx) in block.transactions.iter().enumerate() {
+            let pos = pos as u32;
+            if canonical {
+                self.tx_index.insert(tx.id.clone(), (height, pos));
+            } else {
+                self.tx_index.remove(&tx.id);
+            }
+            for address in indexed_addresses(tx) {
+                if canonical {
+                    self.addr_index.insert((address, height, pos));
+                } else {
+                    self.addr_index.remove(&(address, height, pos));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: RwLock<MemoryInner>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        println!("💾 USING IN-MEMORY STORE (data is lost on exit)");
+        Self::default()
+    }
+}
+
+impl ChainStore for MemoryStore {
+    fn save_block(
+        &self,
+        block: &Block,
+        snn_state: &SnnState,
+        changes: &StateChanges,
+        undo: &UndoLog,
+    ) -> Result<(), StorageError> {
+        let mut inner = self.inner.write().unwrap();
+        let parent_weight = inner.weights.get(&block.header.prev_hash).cloned().unwrap_or_default();
+        inner.insert_known_block(block, parent_weight.extend(block), snn_state);
+        inner.apply_state(changes);
+        inner.undo.insert(block.hash.clone(), undo.clone());
+        inner.canonical.insert(block.header.index, block.hash.clone());
+        inner.set_head(block);
+        inner.index_block(block, true);
+        Ok(())
+    }
+
+    fn save_side_block(&self, block: &Block, weight: &ChainWeight, snn_state: &SnnState) -> Result<(), StorageError> {
+        self.inner.write().unwrap().insert_known_block(block, weight.clone(), snn_state);
+        Ok(())
+    }
+
+    fn apply_reorg(
+        &self,
+        retract: &[Block],
+        enact: &[Block],
+        changes: &StateChanges,
+        undos: &[UndoLog],
+    ) -> Result<(), StorageError> {
+        let mut inner = self.inner.write().unwrap();
+        for block in retract {
+            inner.canonical.remove(&block.header.index);
+            inner.index_block(block, false);
+        }
+        for (block, undo) in enact.iter().zip(undos) {
+            inner.canonical.insert(block.header.index, block.hash.clone());
+            inner.undo.insert(block.hash.clone(), undo.clone());
+            inner.index_block(block, true);
+        }
+        inner.apply_state(changes);
+        if let Some(tip) = enact.last() {
+            inner.set_head(tip);
+        }
+        Ok(())
+    }
+
+    fn reindex(&self) -> Result<u64, StorageError> {
+        let mut inner = self.inner.write().unwrap();
+        inner.tx_index.clear();
+        inner.addr_index.clear();
+
+        // Thân block #1..pruned_below đã bị xóa (pruning / fast sync): chỉ mục chỉ phủ block còn thân
+        let head = inner.head.as_ref().map_or(0, |(height, _)| *height);
+        let canonical: Vec<Block> = std::iter::once(0).chain(inner.pruned_below.max(1)..=head)
+            .filter_map(|height| inner.canonical.get(&height))
+            .filter_map(|hash| inner.blocks.get(hash).cloned())
+            .collect();
+        let mut indexed = 0;
+        for block in &canonical {
+            inner.index_block(block, true);
+            indexed += block.transactions.len() as u64;
+        }
+        Ok(indexed)
+    }
+
+    fn install_snapshot(
+        &self,
+        headers: &[BlockHeader],
+        snapshot: &StateSnapshot,
+        weight: &ChainWeight,
+        changes: &StateChanges,
+    ) -> Result<(), StorageError> {
+        let mut inner = self.inner.write().unwrap();
+        for header in headers {
+            let hash = header.hash();
+            inner.canonical.insert(header.index, hash.clone());
+            inner.headers.insert(hash, header.clone());
+        }
+        let block = &snapshot.block;
+        inner.insert_known_block(block, weight.clone(), &snapshot.snn_state);
+        inner.apply_state(changes);
+        inner.canonical.insert(block.header.index, block.hash.clone());
+        inner.set_head(block);
+        inner.index_block(block, true);
+        inner.snapshot = Some(snapshot.clone());
+        inner.pruned_below = block.header.index;
+        Ok(())
+    }
+
+    fn prune_blocks(&self, below: u64) -> Result<u64, StorageError> {
+        let mut inner = self.inner.write().unwrap();
+        let from = inner.pruned_below.max(1);
+        if below <= from {
+            return Ok(0);
+        }
+        let mut pruned = 0;
+        for height in from..below {
+            let Some(hash) = inner.canonical.get(&height).cloned() else { continue };
+            if let Some(block) = inner.blocks.remove(&hash) {
+                inner.index_block(&block, false);
+                pruned += 1;
+            }
+            inner.snn.remove(&hash);
+            inner.undo.remove(&hash);
+        }
+        inner.pruned_below = below;
+        Ok(pruned)
+    }
+
+    fn get_pruned_height(&self) -> u64 {
+        self.inner.read().unwrap().pruned_below
+    }
+
+    fn save_snapshot(&self, snapshot: &StateSnapshot) -> Result<(), StorageError> {
+        self.inner.write().unwrap().snapshot = Some(snapshot.clone());
+        Ok(())
+    }
+
+    fn get_snapshot(&self) -> Option<StateSnapshot> {
+        self.inner.read().unwrap().snapshot.clone()
+    }
+
+    fn get_canonical_hash(&self, index: u64) -> Option<String> {
+        self.inner.read().unwrap().canonical.get(&index).cloned()
+    }
+
+    fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
+        self.inner.read().unwrap().blocks.get(hash).cloned()
+    }
+
+    fn get_header(&self, hash: &str) -> Option<BlockHeader> {
+        self.inner.read().unwrap().headers.get(hash).cloned()
+    }
+
+    fn has_block(&self, hash: &str) -> bool {
+        self.inner.read().unwrap().blocks.contains_key(hash)
+    }
+
+    fn get_weight(&self, hash: &str) -> Option<ChainWeight> {
+        self.inner.read().unwrap().weights.get(hash).cloned()
+    }
+
+    fn get_snn_state(&self, hash: &str) -> Option<SnnState> {
+        self.inner.read().unwrap().snn.get(hash).cloned()
+    }
+
+    fn get_undo(&self, hash: &str) -> Option<UndoLog> {
+        self.inner.read().unwrap().undo.get(hash).cloned()
+    }
+
+    fn get_height(&self) -> u64 {
+        self.inner.read().unwrap().head.as_ref().map_or(0, |(height, _)| *height)
+    }
+
+    fn get_last_hash(&self) -> String {
+        match &self.inner.read().unwrap().head {
+            Some((_, hash)) => hash.clone(),
+            None => "0".repeat(64), // Genesis prev_hash mặc định
+        }
+    }
+
+    fn get_tx_location(&self, id: &str) -> Option<(u64, u32)> {
+        self.inner.read().unwrap().tx_index.get(id).copied()
+    }
+
+    fn get_address_locations(&self, address: &str, limit: usize) -> Vec<(u64, u32)> {
+        let start = (address.to_string(), 0, 0);
+        let end = (address.to_string(), u64::MAX, u32::MAX);
+        self.inner.read().unwrap().addr_index.range(start..=end)
+            .rev()
+            .take(limit)
+            .map(|(_, height, pos)| (*height, *pos))
+            .collect()
+    }
+
+    fn get_state(&self, key: &str) -> Option<StateValue> {
+        self.inner.read().unwrap().state.get(key).cloned()
+    }
+
+    fn get_state_buckets(&self) -> Vec<[u8; 32]> {
+        let mut digests = vec![[0u8; 32]; STATE_BUCKETS];
+        for (bucket, digest) in &self.inner.read().unwrap().bucket_digests {
+            digests[*bucket as usize] = *digest;
+        }
+        digests
+    }
+
+    fn get_bucket_keys(&self, bucket: u16) -> Vec<String> {
+        self.inner.read().unwrap().bucket_keys.get(&bucket)
+            .map(|keys| keys.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn scan_state(&self, prefix: &str) -> Vec<(String, StateValue)> {
+        self.inner.read().unwrap().state.range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::state::{root_from_buckets, state_root, StateOverlay};
+
+    fn changes(keys: impl Iterator<Item = u64>, value: impl Fn(u64) -> Option<StateValue>) -> StateChanges {
+        keys.map(|i| (format!("acct:{:04}", i), value(i))).collect()
+    }
+
+    fn assert_buckets_match_full_root(store: &MemoryStore) {
+        let entries = store.get_state_entries();
+        assert_eq!(root_from_buckets(&store.get_state_buckets()), state_root(&entries));
+        let indexed: usize = (0..STATE_BUCKETS as u16).map(|b| store.get_bucket_keys(b).len()).sum();
+        assert_eq!(indexed, entries.len());
+    }
+
+    #[test]
+    fn bucket_digests_track_inserts_updates_and_deletes() {
+        let store = MemoryStore::default();
+        assert_buckets_match_full_root(&store);
+
+        store.inner.write().unwrap().apply_state(&changes(0..500, |i| Some(StateValue::Counter(i))));
+        assert_buckets_match_full_root(&store);
+
+        store.inner.write().unwrap().apply_state(&changes((0..500).step_by(2), |i| Some(StateValue::Counter(i * 7))));
+        assert_buckets_match_full_root(&store);
+
+        store.inner.write().unwrap().apply_state(&changes((0..500).step_by(3), |_| None));
+        assert_buckets_match_full_root(&store);
+
+        store.inner.write().unwrap().apply_state(&changes(0..500, |_| None));
+        assert_buckets_match_full_root(&store);
+        assert!(store.inner.read().unwrap().bucket_digests.is_empty());
+    }
+
+    #[test]
+    fn overlay_root_matches_full_recompute() {
+        let store = MemoryStore::default();
+        store.inner.write().unwrap().apply_state(&changes(0..200, |i| Some(StateValue::Counter(i))));
+
+        let pending = changes(150..260, |i| if i % 5 == 0 { None } else { Some(StateValue::Counter(i + 1)) });
+        let mut overlay = StateOverlay::new(&store);
+        overlay.revert(&pending.clone().into_iter().collect());
+
+        let mut expected = store.get_state_entries();
+        for (key, value) in &pending {
+            match value {
+                Some(v) => expected.insert(key.clone(), v.clone()),
+                None => expected.remove(key),
+            };
+        }
+        assert_eq!(overlay.state_root(), state_root(&expected));
+    }
+}
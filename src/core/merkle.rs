@@ -0,0 +1,175 @@
+// src/core/merkle.rs
+// Cây Merkle nhị phân trên hash giao dịch (SHA256).
+// Lá và nút trong dùng tiền tố khác nhau để không thể giả một nút trong thành lá.
+// Nút lẻ ở cuối tầng được đẩy thẳng lên tầng trên (không nhân đôi) để tránh trùng root.
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProofStep {
+    pub hash: String,
+    pub is_left: bool, // Nút anh em nằm bên trái
+}
+
+/// Bằng chứng một giao dịch nằm trong block
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MerkleProof {
+    pub tx_hash: String,
+    pub index: usize,
+    pub steps: Vec<ProofStep>,
+}
+
+fn hash_leaf(tx_hash: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(tx_hash.as_bytes());
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level.chunks(2)
+        .map(|pair| match pair {
+            [l, r] => hash_node(l, r),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Root của danh sách hash giao dịch (block rỗng -> "000...")
+pub fn merkle_root(tx_hashes: &[String]) -> String {
+    if tx_hashes.is_empty() {
+        return "0".repeat(64);
+    }
+    let mut level: Vec<[u8; 32]> = tx_hashes.iter().map(|h| hash_leaf(h)).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    hex::encode(level[0])
+}
+
+/// Tạo bằng chứng cho giao dịch thứ `index`
+pub fn merkle_proof(tx_hashes: &[String], index: usize) -> Option<MerkleProof> {
+    let tx_hash = tx_hashes.get(index)?.clone();
+    let mut level: Vec<[u8; 32]> = tx_hashes.iter().map(|h| hash_leaf(h)).collect();
+    let mut pos = index;
+    let mut steps = Vec::new();
+
+    while level.len() > 1 {
+        let sibling = pos ^ 1;
+        if sibling < level.len() {
+            steps.push(ProofStep { hash: hex::encode(level[sibling]), is_left: sibling < pos });
+        }
+        level = next_level(&level);
+        pos /= 2;
+    }
+
+    Some(MerkleProof { tx_hash, index, steps })
+}
+
+/// Kiểm tra bằng chứng với tx_root trong header của block
+pub fn verify_proof(proof: &MerkleProof, root: &str) -> bool {
+    let mut acc = hash_leaf(&proof.tx_hash);
+    for step in &proof.steps {
+        let sibling: [u8; 32] = match hex::decode(&step.hash).ok().and_then(|b| b.try_into().ok()) {
+            Some(s) => s,
+            None => return false,
+        };
+        acc = if step.is_left { hash_node(&sibling, &acc) } else { hash_node(&acc, &sibling) };
+    }
+    hex::encode(acc) == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(n: usize) -> Vec<String> {
+        (0..n).map(|i| hex::encode(Sha256::digest(i.to_be_bytes()))).collect()
+    }
+
+    #[test]
+    fn root_of_empty_block_is_zero() {
+        assert_eq!(merkle_root(&[]), "0".repeat(64));
+    }
+
+    #[test]
+    fn root_of_single_tx_is_its_leaf_hash() {
+        let txs = hashes(1);
+        assert_eq!(merkle_root(&txs), hex::encode(hash_leaf(&txs[0])));
+    }
+
+    #[test]
+    fn odd_node_is_promoted_not_duplicated() {
+        let txs = hashes(3);
+        let (a, b, c) = (hash_leaf(&txs[0]), hash_leaf(&txs[1]), hash_leaf(&txs[2]));
+        assert_eq!(merkle_root(&txs), hex::encode(hash_node(&hash_node(&a, &b), &c)));
+
+        // Nhân đôi lá cuối phải cho root khác
+        let mut duplicated = txs.clone();
+        duplicated.push(txs[2].clone());
+        assert_ne!(merkle_root(&txs), merkle_root(&duplicated));
+    }
+
+    #[test]
+    fn root_depends_on_order() {
+        let txs = hashes(4);
+        let mut swapped = txs.clone();
+        swapped.swap(1, 2);
+        assert_ne!(merkle_root(&txs), merkle_root(&swapped));
+    }
+
+    #[test]
+    fn proofs_verify_for_every_index() {
+        for n in 1..=9 {
+            let txs = hashes(n);
+            let root = merkle_root(&txs);
+            for index in 0..n {
+                let proof = merkle_proof(&txs, index).unwrap();
+                assert_eq!(proof.tx_hash, txs[index]);
+                assert!(verify_proof(&proof, &root), "proof {} of {} failed", index, n);
+            }
+            assert!(merkle_proof(&txs, n).is_none());
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_are_rejected() {
+        let txs = hashes(5);
+        let root = merkle_root(&txs);
+        let proof = merkle_proof(&txs, 2).unwrap();
+
+        let mut wrong_tx = proof.clone();
+        wrong_tx.tx_hash = txs[3].clone();
+        assert!(!verify_proof(&wrong_tx, &root));
+
+        let mut flipped = proof.clone();
+        flipped.steps[0].is_left = !flipped.steps[0].is_left;
+        assert!(!verify_proof(&flipped, &root));
+
+        let mut bad_hex = proof.clone();
+        bad_hex.steps[0].hash = "zz".to_string();
+        assert!(!verify_proof(&bad_hex, &root));
+
+        assert!(!verify_proof(&proof, &merkle_root(&hashes(4))));
+    }
+
+    #[test]
+    fn inner_node_cannot_pose_as_leaf() {
+        // Root của 2 lá không phải hash_leaf của một "giao dịch" bất kỳ nhờ tiền tố lá/nút khác nhau
+        let txs = hashes(2);
+        let inner = hex::encode(hash_node(&hash_leaf(&txs[0]), &hash_leaf(&txs[1])));
+        assert_ne!(merkle_root(std::slice::from_ref(&inner)), merkle_root(&txs));
+    }
+}
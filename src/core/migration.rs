@@ -0,0 +1,140 @@
+// src/core/migration.rs
+// Phiên bản schema của DB và các bước nâng cấp chạy lúc khởi động.
+// Mỗi lần đổi layout lưu trữ: tăng SCHEMA_VERSION và thêm một hàm vào MIGRATIONS.
+use crate::core::storage::StorageError;
+use sled::{Batch, Db};
+
+pub const SCHEMA_VERSION: u32 = 1;
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+/// Cây chứa fact của DB v0 được nhập lại (key -> value UTF-8)
+pub const LEGACY_FACTS_TREE: &str = "legacy_facts";
+
+type Migration = fn(&Db) -> Result<(), StorageError>;
+
+/// MIGRATIONS[i] nâng schema từ v(i) lên v(i + 1)
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [migrate_v0_to_v1];
+
+/// Phiên bản ghi trong DB (None = DB chưa có dấu phiên bản)
+pub fn read_version(db: &Db) -> Result<Option<u32>, StorageError> {
+    match db.get(SCHEMA_VERSION_KEY)? {
+        Some(raw) => {
+            let bytes = <[u8; 4]>::try_from(raw.as_ref())
+                .map_err(|_| StorageError::Encode("malformed schema_version".to_string()))?;
+            Ok(Some(u32::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn write_version(db: &Db, version: u32) -> Result<(), StorageError> {
+    db.insert(SCHEMA_VERSION_KEY, &version.to_be_bytes())?;
+    db.flush()?;
+    Ok(())
+}
+
+/// Đưa DB lên SCHEMA_VERSION. Từ chối DB do node mới hơn ghi.
+/// Mỗi bước được đánh dấu ngay khi xong nên có thể chạy lại nếu bị ngắt giữa chừng.
+pub fn run(db: &Db) -> Result<(), StorageError> {
+    let found = match read_version(db)? {
+        Some(version) => version,
+        // DB mới tạo: ghi thẳng phiên bản hiện tại
+        None if db.is_empty() => return write_version(db, SCHEMA_VERSION),
+        // Có dữ liệu nhưng không có dấu phiên bản: layout đời đầu
+        None => 0,
+    };
+
+    if found > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedSchema { found, supported: SCHEMA_VERSION });
+    }
+
+    for version in found..SCHEMA_VERSION {
+        println!("🔧 MIGRATING DB SCHEMA v{} -> v{}", version, version + 1);
+        MIGRATIONS[version as usize](db)?;
+        write_version(db, version + 1)?;
+    }
+    Ok(())
+}
+
+/// v0: block JSON nguyên khối ở "block:<index>", fact là UTF-8 thô ở "fact:<key>".
+/// Block v0 không có header/state_root/chain_id nên không thể tái xác thực theo luật hiện tại:
+/// chuỗi v0 bị bỏ, node dựng lại genesis từ chain spec. Dữ liệu cũ được chuyển sang cây "legacy_v0"
+/// (không xóa); fact được nhập lại vào LEGACY_FACTS_TREE để vẫn tra cứu được qua recall_fact,
+/// nhưng không thuộc trạng thái on-chain vì không có block nào chứng minh chúng.
+fn migrate_v0_to_v1(db: &Db) -> Result<(), StorageError> {
+    let legacy = db.open_tree("legacy_v0")?;
+    let legacy_facts = db.open_tree(LEGACY_FACTS_TREE)?;
+    let mut archived = Batch::default();
+    let mut facts = Batch::default();
+    let mut cleared = Batch::default();
+    let (mut count, mut blocks, mut fact_count) = (0, 0, 0);
+
+    for item in db.iter() {
+        let (key, value) = item?;
+        if key.starts_with(b"block:") {
+            blocks += 1;
+        } else if let Some(fact) = key.strip_prefix(b"fact:") {
+            if std::str::from_utf8(&value).is_ok() {
+                facts.insert(fact, value.clone());
+                fact_count += 1;
+            }
+        }
+        archived.insert(key.clone(), value);
+        cleared.remove(key);
+        count += 1;
+    }
+
+    // Ghi bản lưu trữ trước rồi mới xóa: bị ngắt giữa chừng thì lần chạy sau làm lại từ đầu
+    legacy.apply_batch(archived)?;
+    legacy_facts.apply_batch(facts)?;
+    legacy.flush()?;
+    legacy_facts.flush()?;
+    db.apply_batch(cleared)?;
+    println!("📦 Archived {} legacy v0 keys into tree 'legacy_v0'", count);
+    println!("📚 Re-imported {} legacy v0 facts (read-only, not part of on-chain state)", fact_count);
+    println!("⚠️  LEGACY v0 CHAIN DROPPED: {} blocks cannot be re-validated, node restarts from genesis", blocks);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DB v0 dựng tay: block JSON theo index và fact UTF-8 thô, không có dấu phiên bản
+    fn v0_db() -> Db {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.insert("block:0", br#"{"index":0,"transactions":[],"prev_hash":"0"}"#.as_slice()).unwrap();
+        db.insert("block:1", br#"{"index":1,"transactions":[],"prev_hash":"abc"}"#.as_slice()).unwrap();
+        db.insert("fact:sky", "blue").unwrap();
+        db.insert("fact:grass", "green").unwrap();
+        db
+    }
+
+    #[test]
+    fn migrates_v0_db_keeping_facts_and_archiving_old_keys() {
+        let db = v0_db();
+        run(&db).unwrap();
+
+        assert_eq!(read_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        // Cây chính chỉ còn dấu phiên bản
+        assert_eq!(db.len(), 1);
+
+        let legacy = db.open_tree("legacy_v0").unwrap();
+        assert_eq!(legacy.len(), 4);
+        assert!(legacy.get("block:1").unwrap().is_some());
+
+        let facts = db.open_tree(LEGACY_FACTS_TREE).unwrap();
+        assert_eq!(facts.get("sky").unwrap().as_deref(), Some(b"blue".as_slice()));
+        assert_eq!(facts.get("grass").unwrap().as_deref(), Some(b"green".as_slice()));
+
+        // Chạy lại trên DB đã nâng cấp không làm gì
+        run(&db).unwrap();
+        assert_eq!(db.open_tree("legacy_v0").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rejects_db_from_newer_node() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        write_version(&db, SCHEMA_VERSION + 1).unwrap();
+        assert!(matches!(run(&db), Err(StorageError::UnsupportedSchema { .. })));
+    }
+}
@@ -4,3 +4,13 @@ pub mod transaction;
 pub mod wallet;
 pub mod storage;
 pub mod governance;
+pub mod validation;
+pub mod fork_choice;
+pub mod state;
+pub mod merkle;
+pub mod chain_spec;
+pub mod migration;
+pub mod sled_store;
+pub mod memory_store;
+pub mod archive;
+pub mod snapshot;
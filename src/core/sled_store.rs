@@ -0,0 +1,508 @@
+// src/core/sled_store.rs
+// Backend ChainStore trên sled (mặc định của node)
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::{Batch, Db, Transactional, Tree};
+use std::str::from_utf8;
+use crate::core::block::{Block, BlockHeader};
+use crate::core::snapshot::StateSnapshot;
+use crate::core::fork_choice::ChainWeight;
+use crate::core::migration;
+use crate::constants::STATE_BUCKETS;
+use crate::core::state::{rehash_buckets, state_bucket, StateChanges, StateValue, UndoLog};
+use crate::core::storage::{indexed_addresses, ChainStore, StorageError};
+use crate::ai::snn::SnnState;
+
+impl From<TransactionError<()>> for StorageError {
+    fn from(e: TransactionError<()>) -> Self {
+        match e {
+            TransactionError::Storage(e) => StorageError::Db(e),
+            TransactionError::Abort(()) => StorageError::Db(sled::Error::Unsupported("transaction aborted".to_string())),
+        }
+    }
+}
+
+fn encode_json<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, StorageError> {
+    serde_json::to_vec(value).map_err(|e| StorageError::Encode(e.to_string()))
+}
+
+fn encode_bin<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, StorageError> {
+    bincode::serialize(value).map_err(|e| StorageError::Encode(e.to_string()))
+}
+
+pub struct SledStore {
+    db: Db,
+    tx_index: Tree,   // tx id -> (height, vị trí) trên chuỗi chính
+    addr_index: Tree, // <address>:<height BE><vị trí BE> -> tx id
+    legacy_facts: Tree, // Fact của DB v0 (key -> value), xem migration::migrate_v0_to_v1
+}
+
+/// Thay đổi của các cây chỉ mục, ghi cùng một giao dịch sled với cây chính
+#[derive(Default)]
+struct IndexBatch {
+    txs: Batch,
+    addresses: Batch,
+}
+
+impl SledStore {
+    pub fn new(path: &str) -> Result<Self, StorageError> {
+        println!("💾 MOUNTING SLED DB AT: {}", path);
+        // Sled tự động tạo thư mục và file db
+        let db = sled::open(path)?;
+        migration::run(&db)?;
+        let tx_index = db.open_tree("tx_index")?;
+        let addr_index = db.open_tree("addr_index")?;
+        let legacy_facts = db.open_tree(migration::LEGACY_FACTS_TREE)?;
+        Ok(Self { db, tx_index, addr_index, legacy_facts })
+    }
+
+    /// Ghi cây chính và các cây chỉ mục trong một giao dịch sled duy nhất:
+    /// hoặc toàn bộ block (thân, trạng thái, chỉ mục, con trỏ đỉnh) được ghi, hoặc không gì cả.
+    fn commit(&self, batch: Batch, index: IndexBatch) -> Result<(), StorageError> {
+        (&*self.db, &self.tx_index, &self.addr_index)
+            .transaction(|(main, txs, addresses)| {
+                main.apply_batch(&batch)?;
+                txs.apply_batch(&index.txs)?;
+                addresses.apply_batch(&index.addresses)?;
+                Ok::<(), ConflictableTransactionError<()>>(())
+            })?;
+        // Flush để đảm bảo dữ liệu ghi xuống ổ cứng
+        self.db.flush()?;
+        Ok(())
+    }
+
+    // --- Block Methods ---
+    // Mọi block đã biết (kể cả nhánh phụ) nằm ở "blk:<hash>", header riêng ở "hdr:<hash>",
+    // còn "block:<index>" chỉ giữ hash của block trên chuỗi chính (canonical).
+
+    /// `canonical = true`: thêm chỉ mục cho giao dịch của block, `false`: gỡ bỏ (block bị retract)
+    fn stage_tx_index(index: &mut IndexBatch, block: &Block, canonical: bool) -> Result<(), StorageError> {
+        let height = block.header.index;
+        for (pos, tx) in block.transactions.iter().enumerate() {
+            let location = encode_bin(&(height, pos as u32))?;
+            if canonical {
+                index.txs.insert(tx.id.as_bytes(), location);
+            } else {
+                index.txs.remove(tx.id.as_bytes());
+            }
+            for address in indexed_addresses(tx) {
+                let key = Self::addr_index_key(&address, height, pos as u32);
+                if canonical {
+                    index.addresses.insert(key, tx.id.as_bytes());
+                } else {
+                    index.addresses.remove(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn addr_index_key(address: &str, height: u64, pos: u32) -> Vec<u8> {
+        let mut key = format!("{}:", address).into_bytes();
+        key.extend_from_slice(&height.to_be_bytes());
+        key.extend_from_slice(&pos.to_be_bytes());
+        key
+    }
+
+    fn stage_known_block(batch: &mut Batch, block: &Block, weight: &ChainWeight) -> Result<(), StorageError> {
+        batch.insert(format!("blk:{}", block.hash).as_bytes(), encode_json(block)?);
+        batch.insert(format!("hdr:{}", block.hash).as_bytes(), encode_bin(&block.header)?);
+        batch.insert(format!("weight:{}", block.hash).as_bytes(), encode_json(weight)?);
+        Ok(())
+    }
+
+    fn stage_snn_state(batch: &mut Batch, hash: &str, snn_state: &SnnState) -> Result<(), StorageError> {
+        batch.insert(format!("snn:{}", hash).as_bytes(), encode_bin(snn_state)?);
+        Ok(())
+    }
+
+    /// Ghi thay đổi trạng thái kèm chỉ mục key theo bucket ("sbkey:<bucket>:<key>")
+    /// và digest mới của các bucket bị chạm tới ("sbucket:<bucket>", bucket rỗng thì xóa)
+    fn stage_state(&self, batch: &mut Batch, changes: &StateChanges) -> Result<(), StorageError> {
+        let digests = rehash_buckets(changes, |bucket| self.get_bucket_keys(bucket), |key| self.get_state(key));
+        for (key, value) in changes {
+            let bucket_key = Self::bucket_key_entry(state_bucket(key), key);
+            match value {
+                Some(v) => {
+                    batch.insert(key.as_bytes(), encode_json(v)?);
+                    batch.insert(bucket_key, &[]);
+                }
+                None => {
+                    batch.remove(key.as_bytes());
+                    batch.remove(bucket_key);
+                }
+            }
+        }
+        for (bucket, digest) in digests {
+            let key = format!("sbucket:{:03x}", bucket);
+            if digest == [0u8; 32] {
+                batch.remove(key.as_bytes());
+            } else {
+                batch.insert(key.as_bytes(), &digest);
+            }
+        }
+        Ok(())
+    }
+
+    fn bucket_key_entry(bucket: u16, key: &str) -> Vec<u8> {
+        format!("sbkey:{:03x}:{}", bucket, key).into_bytes()
+    }
+
+    fn stage_undo(batch: &mut Batch, hash: &str, undo: &UndoLog) -> Result<(), StorageError> {
+        batch.insert(format!("undo:{}", hash).as_bytes(), encode_json(undo)?);
+        Ok(())
+    }
+
+    fn stage_canonical_block(batch: &mut Batch, block: &Block) {
+        // Key: "block:<index>" -> hash
+        batch.insert(format!("block:{}", block.header.index).as_bytes(), block.hash.as_bytes());
+    }
+
+    fn stage_head(batch: &mut Batch, block: &Block) {
+        // Cập nhật chiều cao và hash mới nhất
+        batch.insert("chain_height", &block.header.index.to_be_bytes());
+        batch.insert("last_hash", block.hash.as_bytes());
+    }
+}
+
+impl ChainStore for SledStore {
+    fn save_block(
+        &self,
+        block: &Block,
+        snn_state: &SnnState,
+        changes: &StateChanges,
+        undo: &UndoLog,
+    ) -> Result<(), StorageError> {
+        let parent_weight = self.get_weight(&block.header.prev_hash).unwrap_or_default();
+        let mut batch = Batch::default();
+        Self::stage_known_block(&mut batch, block, &parent_weight.extend(block))?;
+        Self::stage_snn_state(&mut batch, &block.hash, snn_state)?;
+        self.stage_state(&mut batch, changes)?;
+        Self::stage_undo(&mut batch, &block.hash, undo)?;
+        Self::stage_canonical_block(&mut batch, block);
+        Self::stage_head(&mut batch, block);
+
+        let mut index = IndexBatch::default();
+        Self::stage_tx_index(&mut index, block, true)?;
+        self.commit(batch, index)
+    }
+
+    fn save_side_block(&self, block: &Block, weight: &ChainWeight, snn_state: &SnnState) -> Result<(), StorageError> {
+        let mut batch = Batch::default();
+        Self::stage_known_block(&mut batch, block, weight)?;
+        Self::stage_snn_state(&mut batch, &block.hash, snn_state)?;
+        self.db.apply_batch(batch)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn apply_reorg(
+        &self,
+        retract: &[Block],
+        enact: &[Block],
+        changes: &StateChanges,
+        undos: &[UndoLog],
+    ) -> Result<(), StorageError> {
+        let mut batch = Batch::default();
+        let mut index = IndexBatch::default();
+        for block in retract {
+            batch.remove(format!("block:{}", block.header.index).as_bytes());
+            Self::stage_tx_index(&mut index, block, false)?;
+        }
+        for (block, undo) in enact.iter().zip(undos) {
+            Self::stage_canonical_block(&mut batch, block);
+            Self::stage_undo(&mut batch, &block.hash, undo)?;
+            Self::stage_tx_index(&mut index, block, true)?;
+        }
+        self.stage_state(&mut batch, changes)?;
+        if let Some(tip) = enact.last() {
+            Self::stage_head(&mut batch, tip);
+        }
+        self.commit(batch, index)
+    }
+
+    fn reindex(&self) -> Result<u64, StorageError> {
+        self.tx_index.clear()?;
+        self.addr_index.clear()?;
+
+        // Thân block #1..pruned_below đã bị xóa (pruning / fast sync): chỉ mục chỉ phủ block còn thân
+        let mut indexed = 0;
+        let first = self.get_pruned_height().max(1);
+        for height in std::iter::once(0).chain(first..=self.get_height()) {
+            let Some(block) = self.get_block(height) else { continue };
+            let mut index = IndexBatch::default();
+            Self::stage_tx_index(&mut index, &block, true)?;
+            self.tx_index.apply_batch(index.txs)?;
+            self.addr_index.apply_batch(index.addresses)?;
+            indexed += block.transactions.len() as u64;
+        }
+        self.db.flush()?;
+        Ok(indexed)
+    }
+
+    fn install_snapshot(
+        &self,
+        headers: &[BlockHeader],
+        snapshot: &StateSnapshot,
+        weight: &ChainWeight,
+        changes: &StateChanges,
+    ) -> Result<(), StorageError> {
+        let mut batch = Batch::default();
+        for header in headers {
+            let hash = header.hash();
+            batch.insert(format!("hdr:{}", hash).as_bytes(), encode_bin(header)?);
+            batch.insert(format!("block:{}", header.index).as_bytes(), hash.as_bytes());
+        }
+        let block = &snapshot.block;
+        Self::stage_known_block(&mut batch, block, weight)?;
+        Self::stage_snn_state(&mut batch, &block.hash, &snapshot.snn_state)?;
+        self.stage_state(&mut batch, changes)?;
+        Self::stage_canonical_block(&mut batch, block);
+        Self::stage_head(&mut batch, block);
+        batch.insert("snapshot", encode_json(snapshot)?);
+        batch.insert("pruned_below", &block.header.index.to_be_bytes());
+
+        let mut index = IndexBatch::default();
+        Self::stage_tx_index(&mut index, block, true)?;
+        self.commit(batch, index)
+    }
+
+    fn prune_blocks(&self, below: u64) -> Result<u64, StorageError> {
+        let from = self.get_pruned_height().max(1);
+        if below <= from {
+            return Ok(0);
+        }
+        let mut batch = Batch::default();
+        let mut index = IndexBatch::default();
+        let mut pruned = 0;
+        for height in from..below {
+            let Some(hash) = self.get_canonical_hash(height) else { continue };
+            if let Some(block) = self.get_block_by_hash(&hash) {
+                Self::stage_tx_index(&mut index, &block, false)?;
+                pruned += 1;
+            }
+            batch.remove(format!("blk:{}", hash).as_bytes());
+            batch.remove(format!("snn:{}", hash).as_bytes());
+            batch.remove(format!("undo:{}", hash).as_bytes());
+        }
+        batch.insert("pruned_below", &below.to_be_bytes());
+        self.commit(batch, index)?;
+        Ok(pruned)
+    }
+
+    fn get_pruned_height(&self) -> u64 {
+        if let Ok(Some(val)) = self.db.get("pruned_below") {
+            if let Ok(arr) = <[u8; 8]>::try_from(val.as_ref()) {
+                return u64::from_be_bytes(arr);
+            }
+        }
+        0
+    }
+
+    fn save_snapshot(&self, snapshot: &StateSnapshot) -> Result<(), StorageError> {
+        self.db.insert("snapshot", encode_json(snapshot)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn get_snapshot(&self) -> Option<StateSnapshot> {
+        if let Ok(Some(value)) = self.db.get("snapshot") {
+            return serde_json::from_slice(&value).ok();
+        }
+        None
+    }
+
+    fn get_canonical_hash(&self, index: u64) -> Option<String> {
+        let key = format!("block:{}", index);
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return String::from_utf8(value.to_vec()).ok();
+        }
+        None
+    }
+
+    fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
+        let key = format!("blk:{}", hash);
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return serde_json::from_slice(&value).ok();
+        }
+        None
+    }
+
+    fn get_header(&self, hash: &str) -> Option<BlockHeader> {
+        let key = format!("hdr:{}", hash);
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return bincode::deserialize(&value).ok();
+        }
+        None
+    }
+
+    fn has_block(&self, hash: &str) -> bool {
+        self.db.contains_key(format!("blk:{}", hash).as_bytes()).unwrap_or(false)
+    }
+
+    fn get_weight(&self, hash: &str) -> Option<ChainWeight> {
+        let key = format!("weight:{}", hash);
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return serde_json::from_slice(&value).ok();
+        }
+        None
+    }
+
+    fn get_snn_state(&self, hash: &str) -> Option<SnnState> {
+        let key = format!("snn:{}", hash);
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return bincode::deserialize(&value).ok();
+        }
+        None
+    }
+
+    fn get_undo(&self, hash: &str) -> Option<UndoLog> {
+        let key = format!("undo:{}", hash);
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return serde_json::from_slice(&value).ok();
+        }
+        None
+    }
+
+    fn get_height(&self) -> u64 {
+        if let Ok(Some(val)) = self.db.get("chain_height") {
+            if let Ok(arr) = <[u8; 8]>::try_from(val.as_ref()) {
+                return u64::from_be_bytes(arr);
+            }
+        }
+        0 // Mặc định là 0 nếu chưa có block nào
+    }
+
+    fn get_last_hash(&self) -> String {
+        if let Ok(Some(val)) = self.db.get("last_hash") {
+            return String::from_utf8(val.to_vec()).unwrap_or_else(|_| "0".repeat(64));
+        }
+        "0".repeat(64) // Genesis prev_hash mặc định
+    }
+
+    fn get_tx_location(&self, id: &str) -> Option<(u64, u32)> {
+        let location = self.tx_index.get(id.as_bytes()).ok()??;
+        bincode::deserialize(&location).ok()
+    }
+
+    fn get_address_locations(&self, address: &str, limit: usize) -> Vec<(u64, u32)> {
+        let prefix = format!("{}:", address);
+        self.addr_index.scan_prefix(prefix.as_bytes())
+            .rev()
+            .take(limit)
+            .filter_map(|item| {
+                let (key, _) = item.ok()?;
+                let location = <[u8; 12]>::try_from(key.get(prefix.len()..)?).ok()?;
+                let height = u64::from_be_bytes(location[..8].try_into().ok()?);
+                let pos = u32::from_be_bytes(location[8..].try_into().ok()?);
+                Some((height, pos))
+            })
+            .collect()
+    }
+
+    fn get_state(&self, key: &str) -> Option<StateValue> {
+        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
+            return serde_json::from_slice(&value).ok();
+        }
+        None
+    }
+
+    fn get_legacy_fact(&self, key: &str) -> Option<String> {
+        let value = self.legacy_facts.get(key.as_bytes()).ok()??;
+        from_utf8(&value).ok().map(str::to_string)
+    }
+
+    fn get_state_buckets(&self) -> Vec<[u8; 32]> {
+        let mut digests = vec![[0u8; 32]; STATE_BUCKETS];
+        for (key, value) in self.db.scan_prefix("sbucket:").filter_map(|item| item.ok()) {
+            let bucket = from_utf8(&key["sbucket:".len()..]).ok().and_then(|hex| usize::from_str_radix(hex, 16).ok());
+            if let (Some(bucket), Ok(digest)) = (bucket, <[u8; 32]>::try_from(value.as_ref())) {
+                if bucket < STATE_BUCKETS {
+                    digests[bucket] = digest;
+                }
+            }
+        }
+        digests
+    }
+
+    fn get_bucket_keys(&self, bucket: u16) -> Vec<String> {
+        let prefix = Self::bucket_key_entry(bucket, "");
+        self.db.scan_prefix(&prefix)
+            .filter_map(|item| item.ok())
+            .filter_map(|(key, _)| Some(from_utf8(&key[prefix.len()..]).ok()?.to_string()))
+            .collect()
+    }
+
+    fn scan_state(&self, prefix: &str) -> Vec<(String, StateValue)> {
+        self.db.scan_prefix(prefix)
+            .filter_map(|item| item.ok())
+            .filter_map(|(k, v)| Some((from_utf8(&k).ok()?.to_string(), serde_json::from_slice(&v).ok()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::snn_core::consensus_snn;
+    use crate::core::chain_spec::ensure_genesis;
+    use crate::core::state::{total_fees, StateOverlay, UndoRecorder};
+    use crate::core::transaction::tests::{address_of, test_key, test_spec, transfer};
+
+    const MINER: &str = "PAPPAP000000000000000000000000000000A1";
+
+    /// Mở lại DB vừa đóng. Luồng I/O nền của sled có thể còn giữ khóa file một lúc sau khi drop.
+    fn reopen(path: &std::path::Path) -> SledStore {
+        for _ in 0..50 {
+            match SledStore::new(path.to_str().unwrap()) {
+                Ok(store) => return store,
+                Err(_) => std::thread::sleep(std::time::Duration::from_millis(20)),
+            }
+        }
+        SledStore::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn save_block_commits_block_state_and_indexes_together() {
+        let alice = test_key(1);
+        let spec = test_spec(&[&alice], 1_000_000);
+        let path = std::env::temp_dir().join(format!("pappap-sled-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+
+        let tx = transfer(&alice, 1_000, 10, 0);
+        let block = {
+            let storage = SledStore::new(path.to_str().unwrap()).unwrap();
+            let genesis_hash = ensure_genesis(&storage, &spec).unwrap();
+            let snn = consensus_snn(&storage, &spec.snn, &genesis_hash).unwrap();
+            let proof = snn.prove(1);
+
+            let mut overlay = StateOverlay::new(&storage);
+            let mut undo = UndoRecorder::default();
+            overlay.apply_tx(&tx, &mut undo).unwrap();
+            let txs = vec![tx.clone()];
+            overlay.apply_coinbase(MINER, spec.emission.reward_at(1), total_fees(&txs), &mut undo).unwrap();
+            let block = Block::new(1, genesis_hash, txs, MINER.to_string(), &proof, overlay.state_root(), &spec.forbidden_genes);
+            let changes = overlay.into_changes();
+            storage.save_block(&block, &proof.state, &changes, &undo.into_log()).unwrap();
+            block
+        };
+
+        // Mở lại DB: thân block, trạng thái, chỉ mục và con trỏ đỉnh cùng có mặt
+        let storage = reopen(&path);
+        assert_eq!(storage.get_last_hash(), block.hash);
+        assert_eq!(storage.get_block(1).unwrap().hash, block.hash);
+        assert_eq!(storage.get_tx_location(&tx.id), Some((1, 0)));
+        assert_eq!(storage.get_address_locations(&address_of(&alice), 10), vec![(1, 0)]);
+        assert_eq!(storage.get_account(&address_of(&alice)).unwrap().nonce, 1);
+        assert!(storage.get_undo(&block.hash).is_some());
+        drop(storage);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn open_failure_is_returned_instead_of_panicking() {
+        let path = std::env::temp_dir().join(format!("pappap-sled-not-a-dir-{}", std::process::id()));
+        std::fs::write(&path, b"not a database").unwrap();
+        assert!(matches!(SledStore::new(path.to_str().unwrap()), Err(StorageError::Db(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+// src/core/snapshot.rs
+// Snapshot trạng thái & fast sync: node mới tải trạng thái tại một block từ peer
+// thay vì thực thi lại toàn bộ chuỗi từ genesis.
+//
+// Cam kết trong header:
+// - state_root của block h  -> toàn bộ trạng thái on-chain (tài khoản, DAO, fact, thưởng, tổng cung) sau block h
+// - snn_root của block h + 1 -> trạng thái SNN sau block h (SNN của block cha dùng để chấm block con)
+// Vì vậy snapshot tại h chỉ kiểm chứng được khi chuỗi header đã có block h + 1.
+use crate::ai::snn::{SnnParams, SnnState, SNN};
+use crate::core::block::{Block, BlockHeader};
+use crate::core::chain_spec::ChainSpec;
+use crate::core::fork_choice::ChainWeight;
+use crate::core::state::{state_root, StateChanges, StateValue, STATE_PREFIXES};
+use crate::core::storage::{ChainStore, StorageError};
+use crate::constants::{FAST_SYNC_MIN_PEERS, FAST_SYNC_WAIT_SECS, SYNC_MAX_HEADERS};
+use crate::network::sync::{ChainStatus, SyncClient, SyncError};
+use libp2p::futures::future;
+use libp2p::PeerId;
+use serde::{Serialize, Deserialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Trạng thái đầy đủ ngay sau `block`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateSnapshot {
+    pub block: Block,
+    pub entries: BTreeMap<String, StateValue>,
+    pub snn_state: SnnState,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Sync(SyncError),
+    NotEnoughPeers { found: usize, needed: usize },
+    BrokenHeaderChain { index: u64 },
+    GenesisMismatch { local: String, remote: String },
+    Unconfirmed(u64), // Peer chưa có header h + 1 để cam kết trạng thái SNN
+    HeaderMismatch(u64),
+    WeightMismatch { claimed: ChainWeight, computed: ChainWeight }, // Chuỗi header không khớp trạng thái peer báo
+    NoSnapshot,
+    Unbacked { height: u64, backers: usize }, // Không đủ peer xác nhận header của snapshot
+    InvalidKey(String),
+    StateRootMismatch { claimed: String, computed: String },
+    SnnRootMismatch { claimed: String, computed: String },
+    SnnShape, // Trạng thái SNN không khớp kích thước mạng trong chain spec
+    Storage(StorageError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Sync(e) => write!(f, "{}", e),
+            SnapshotError::NotEnoughPeers { found, needed } => write!(f, "found {} peer(s) on this chain, need {}", found, needed),
+            SnapshotError::BrokenHeaderChain { index } => write!(f, "header #{} does not link to its parent", index),
+            SnapshotError::GenesisMismatch { local, remote } => write!(f, "peer genesis {} differs from local {}", remote, local),
+            SnapshotError::Unconfirmed(height) => write!(f, "snapshot #{} has no successor header yet, retry later", height),
+            SnapshotError::HeaderMismatch(height) => write!(f, "snapshot block #{} is not on the header chain", height),
+            SnapshotError::WeightMismatch { claimed, computed } => write!(
+                f, "header chain weighs {}/{} but the peer reported {}/{}",
+                computed.height, computed.total_spike, claimed.height, claimed.total_spike
+            ),
+            SnapshotError::NoSnapshot => write!(f, "no peer serves a snapshot"),
+            SnapshotError::Unbacked { height, backers } => write!(
+                f, "snapshot #{} is confirmed by {} peer(s), need {}", height, backers, FAST_SYNC_MIN_PEERS
+            ),
+            SnapshotError::InvalidKey(key) => write!(f, "snapshot contains non-state key {}", key),
+            SnapshotError::StateRootMismatch { claimed, computed } => write!(f, "state root mismatch: header {}, snapshot {}", claimed, computed),
+            SnapshotError::SnnRootMismatch { claimed, computed } => write!(f, "SNN root mismatch: header {}, snapshot {}", claimed, computed),
+            SnapshotError::SnnShape => write!(f, "SNN state does not match the network shape in the chain spec"),
+            SnapshotError::Storage(e) => write!(f, "storage failure: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<StorageError> for SnapshotError {
+    fn from(e: StorageError) -> Self {
+        SnapshotError::Storage(e)
+    }
+}
+
+impl From<SyncError> for SnapshotError {
+    fn from(e: SyncError) -> Self {
+        SnapshotError::Sync(e)
+    }
+}
+
+impl StateSnapshot {
+    /// Chụp trạng thái tại đỉnh chuỗi chính. Caller phải giữ khóa ghi để không có block mới chen vào.
+    pub fn capture<S: ChainStore>(storage: &S) -> Option<Self> {
+        let block = storage.get_block(storage.get_height())?;
+        let snn_state = storage.get_snn_state(&block.hash)?;
+        Some(Self { entries: storage.get_state_entries(), snn_state, block })
+    }
+
+    pub fn height(&self) -> u64 {
+        self.block.header.index
+    }
+
+    /// Đối chiếu snapshot với chuỗi header đã kiểm tra (`headers[i]` là header #i).
+    /// Trả về trọng lượng nhánh tại block của snapshot.
+    pub fn verify(&self, headers: &[BlockHeader], params: &SnnParams) -> Result<ChainWeight, SnapshotError> {
+        let height = self.height();
+        let header = headers.get(height as usize)
+            .filter(|h| **h == self.block.header && h.hash() == self.block.hash)
+            .ok_or(SnapshotError::HeaderMismatch(height))?;
+        let next = headers.get(height as usize + 1).ok_or(SnapshotError::Unconfirmed(height))?;
+
+        if let Some(key) = self.entries.keys().find(|k| !STATE_PREFIXES.iter().any(|p| k.starts_with(p))) {
+            return Err(SnapshotError::InvalidKey(key.clone()));
+        }
+        let computed = state_root(&self.entries);
+        if computed != header.state_root {
+            return Err(SnapshotError::StateRootMismatch { claimed: header.state_root.clone(), computed });
+        }
+        let computed = SNN::restore(params, &self.snn_state)
+            .ok_or(SnapshotError::SnnShape)?
+            .state_hash();
+        if computed != next.snn_root {
+            return Err(SnapshotError::SnnRootMismatch { claimed: next.snn_root.clone(), computed });
+        }
+        // Thân block phải khớp tx_root trong header
+        if self.block.calculate_hash() != self.block.hash || self.block.header.tx_root != self.block.calculate_tx_root() {
+            return Err(SnapshotError::HeaderMismatch(height));
+        }
+
+        Ok(headers[..=height as usize].iter()
+            .fold(ChainWeight::default(), |weight, h| weight.extend_header(h)))
+    }
+}
+
+/// Chuỗi header phải liền mạch từ genesis của chain spec
+pub fn verify_header_chain(genesis_hash: &str, headers: &[BlockHeader]) -> Result<(), SnapshotError> {
+    let remote = headers.first().map(|h| h.hash()).unwrap_or_default();
+    if remote != genesis_hash {
+        return Err(SnapshotError::GenesisMismatch { local: genesis_hash.to_string(), remote });
+    }
+    for (i, pair) in headers.windows(2).enumerate() {
+        let (parent, child) = (&pair[0], &pair[1]);
+        if child.index != i as u64 + 1 || child.prev_hash != parent.hash() || child.timestamp < parent.timestamp {
+            return Err(SnapshotError::BrokenHeaderChain { index: i as u64 + 1 });
+        }
+    }
+    Ok(())
+}
+
+/// Thay toàn bộ trạng thái local bằng trạng thái của snapshot
+fn replace_state<S: ChainStore>(storage: &S, snapshot: &StateSnapshot) -> StateChanges {
+    let mut changes: StateChanges = storage.get_state_entries().into_keys().map(|k| (k, None)).collect();
+    changes.extend(snapshot.entries.iter().map(|(k, v)| (k.clone(), Some(v.clone()))));
+    changes
+}
+
+/// Chờ tới khi có ít nhất FAST_SYNC_MIN_PEERS peer cùng chain id trả lời trạng thái
+async fn gather_peers(spec: &ChainSpec, client: &SyncClient) -> Result<Vec<(PeerId, ChainStatus)>, SnapshotError> {
+    let deadline = Instant::now() + Duration::from_secs(FAST_SYNC_WAIT_SECS);
+    loop {
+        let peers = client.peers().await;
+        let statuses = future::join_all(peers.iter().map(|peer| client.status(*peer))).await;
+        let found: Vec<(PeerId, ChainStatus)> = peers.into_iter()
+            .zip(statuses)
+            .filter_map(|(peer, status)| Some((peer, status.ok()?)))
+            .filter(|(_, s)| s.chain_id == spec.chain_id)
+            .collect();
+        if found.len() >= FAST_SYNC_MIN_PEERS {
+            return Ok(found);
+        }
+        if Instant::now() >= deadline {
+            return Err(SnapshotError::NotEnoughPeers { found: found.len(), needed: FAST_SYNC_MIN_PEERS });
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Chuỗi header #0..=#status.height của `peer`. Phải liền mạch từ genesis, kết thúc ở head peer báo
+/// và có đúng trọng lượng peer báo (tính lại từ header, không tin con số của peer).
+async fn download_header_chain(
+    client: &SyncClient,
+    genesis_hash: &str,
+    peer: PeerId,
+    status: &ChainStatus,
+) -> Result<Vec<BlockHeader>, SnapshotError> {
+    let mut headers: Vec<BlockHeader> = Vec::new();
+    while (headers.len() as u64) <= status.height {
+        let page = client.headers(peer, headers.len() as u64, SYNC_MAX_HEADERS).await?;
+        if page.is_empty() {
+            break;
+        }
+        headers.extend(page);
+    }
+    headers.truncate(status.height as usize + 1);
+    verify_header_chain(genesis_hash, &headers)?;
+
+    let weight = headers.iter().fold(ChainWeight::default(), |weight, h| weight.extend_header(h));
+    let head = headers.last().map(|h| h.hash()).unwrap_or_default();
+    if weight != status.weight || head != status.head_hash {
+        return Err(SnapshotError::WeightMismatch { claimed: status.weight.clone(), computed: weight });
+    }
+    Ok(headers)
+}
+
+/// Số peer có header của snapshot (#h và #h + 1, header cam kết trạng thái SNN) trên chuỗi chính của mình
+async fn count_backers(client: &SyncClient, peers: &[(PeerId, ChainStatus)], headers: &[BlockHeader], height: u64) -> usize {
+    let expected = &headers[height as usize..=height as usize + 1];
+    let answers = future::join_all(peers.iter().map(|(peer, _)| client.headers(*peer, height, 2))).await;
+    answers.into_iter().filter(|answer| answer.as_deref().is_ok_and(|found| found == expected)).count()
+}
+
+/// Fast sync qua giao thức sync P2P:
+/// 1. chờ đủ FAST_SYNC_MIN_PEERS peer, chọn peer có nhánh nặng nhất theo trạng thái các peer báo,
+/// 2. tải chuỗi header từ genesis, kiểm tra liên kết và trọng lượng khớp với trạng thái của peer đó,
+/// 3. tải snapshot, đối chiếu với header; header của snapshot phải được hơn một peer xác nhận,
+/// 4. ghi snapshot làm đỉnh chuỗi. Block sau snapshot do vòng lặp sync tải và kiểm tra đầy đủ.
+///
+/// Các block trước snapshot chỉ có header: node không thực thi lại chúng.
+pub async fn fast_sync<S: ChainStore>(storage: &S, spec: &ChainSpec, client: &SyncClient) -> Result<u64, SnapshotError> {
+    // 1. Peer
+    let mut peers = gather_peers(spec, client).await?;
+    // Nặng nhất trước (cùng thứ tự với ChainWeight::is_heavier_than)
+    peers.sort_by_key(|(_, s)| Reverse((s.weight.height, s.weight.total_spike)));
+    let (best, best_status) = &peers[0];
+
+    // 2. Header chain
+    let genesis_hash = storage.get_canonical_hash(0).unwrap_or_default();
+    let headers = download_header_chain(client, &genesis_hash, *best, best_status).await?;
+    println!("🧾 FAST SYNC: verified {} headers from {} (head #{})", headers.len(), best, best_status.height);
+
+    // 3. Snapshot: thử lần lượt các peer, lấy snapshot đầu tiên khớp header và được xác nhận
+    let mut last_error = SnapshotError::NoSnapshot;
+    for (peer, _) in &peers {
+        let Some(snapshot) = client.snapshot(*peer).await? else { continue };
+        let weight = match snapshot.verify(&headers, &spec.snn) {
+            Ok(weight) => weight,
+            Err(e) => {
+                println!("⚠️ FAST SYNC: snapshot from {} rejected: {}", peer, e);
+                last_error = e;
+                continue;
+            }
+        };
+        let height = snapshot.height();
+        let backers = count_backers(client, &peers, &headers, height).await;
+        if backers < FAST_SYNC_MIN_PEERS {
+            println!("⚠️ FAST SYNC: snapshot #{} from {} is backed by {} peer(s)", height, peer, backers);
+            last_error = SnapshotError::Unbacked { height, backers };
+            continue;
+        }
+
+        // 4. Ghi snapshot
+        if height > storage.get_height() {
+            let changes = replace_state(storage, &snapshot);
+            storage.install_snapshot(&headers[1..height as usize], &snapshot, &weight, &changes)?;
+            println!("📸 FAST SYNC: installed snapshot at block #{} ({} state entries, {} peers agree)",
+                height, snapshot.entries.len(), backers);
+        }
+        return Ok(storage.get_height());
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::chain::tests::{test_node, MINER_A, MINER_B};
+    use crate::core::state::StateOverlay;
+    use crate::core::transaction::tests::{test_key, test_spec};
+    use crate::network::sync::local_status;
+    use crate::network::sync::tests::fake_network;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn fast_sync_installs_snapshot_confirmed_by_two_peers() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let (b, c) = (test_node(&spec, MINER_B).await, test_node(&spec, MINER_A).await);
+        // B chụp snapshot tại #2, chuỗi đi tiếp tới #4; C theo cùng chuỗi nhưng không có snapshot
+        b.mine_block(true).await.unwrap();
+        b.mine_block(true).await.unwrap();
+        b.storage.save_snapshot(&StateSnapshot::capture(&*b.storage).unwrap()).unwrap();
+        b.mine_block(true).await.unwrap();
+        b.mine_block(true).await.unwrap();
+        for height in 1..=4 {
+            c.import_block(b.storage.get_block(height).unwrap()).await.unwrap();
+        }
+
+        let local = test_node(&spec, MINER_A).await;
+        let (client, _) = fake_network(spec.clone(), vec![b.storage.clone(), c.storage.clone()]);
+        assert_eq!(fast_sync(&*local.storage, &spec, &client).await.unwrap(), 2);
+        assert_eq!(local.storage.get_last_hash(), b.storage.get_canonical_hash(2).unwrap());
+        let header = b.storage.get_block(2).unwrap().header;
+        assert_eq!(StateOverlay::new(&*local.storage).state_root(), header.state_root);
+    }
+
+    #[tokio::test]
+    async fn fast_sync_rejects_snapshot_only_one_peer_confirms() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let (b, d) = (test_node(&spec, MINER_B).await, test_node(&spec, MINER_A).await);
+        let first = b.mine_block(true).await.unwrap();
+        b.mine_block(true).await.unwrap();
+        b.storage.save_snapshot(&StateSnapshot::capture(&*b.storage).unwrap()).unwrap();
+        b.mine_block(true).await.unwrap();
+        // D chung block #1 rồi rẽ nhánh ngắn hơn
+        d.import_block(first).await.unwrap();
+        d.mine_block(true).await.unwrap();
+
+        let local = test_node(&spec, MINER_A).await;
+        let (client, _) = fake_network(spec.clone(), vec![b.storage.clone(), d.storage.clone()]);
+        let result = fast_sync(&*local.storage, &spec, &client).await;
+        assert!(matches!(result, Err(SnapshotError::Unbacked { height: 2, backers: 1 })));
+        assert_eq!(local.storage.get_height(), 0);
+    }
+
+    #[tokio::test]
+    async fn header_chain_must_match_reported_weight() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let b = test_node(&spec, MINER_B).await;
+        b.mine_block(true).await.unwrap();
+        b.mine_block(true).await.unwrap();
+        let (client, ids) = fake_network(spec.clone(), vec![b.storage.clone()]);
+        let genesis = b.storage.get_canonical_hash(0).unwrap();
+
+        let mut status = local_status(&*b.storage, &spec);
+        assert_eq!(download_header_chain(&client, &genesis, ids[0], &status).await.unwrap().len(), 3);
+        // Peer khai trọng lượng lớn hơn chuỗi header nó gửi
+        status.weight.total_spike += 1;
+        let result = download_header_chain(&client, &genesis, ids[0], &status).await;
+        assert!(matches!(result, Err(SnapshotError::WeightMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn snapshot_verified_against_headers_installs_as_head() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let b = test_node(&spec, MINER_B).await;
+        b.mine_block(true).await.unwrap();
+        b.mine_block(true).await.unwrap();
+        let snapshot = StateSnapshot::capture(&*b.storage).unwrap();
+        // Header #3 cam kết trạng thái SNN sau block #2
+        let headers = b.storage.get_headers(0, 10);
+        assert!(matches!(snapshot.verify(&headers, &spec.snn), Err(SnapshotError::Unconfirmed(2))));
+        b.mine_block(true).await.unwrap();
+        let headers = b.storage.get_headers(0, 10);
+        verify_header_chain(&b.storage.get_canonical_hash(0).unwrap(), &headers).unwrap();
+        let weight = snapshot.verify(&headers, &spec.snn).unwrap();
+        assert_eq!(Some(weight.clone()), b.storage.get_weight(&snapshot.block.hash));
+
+        let local = test_node(&spec, MINER_A).await;
+        let changes = replace_state(&*local.storage, &snapshot);
+        local.storage.install_snapshot(&headers[1..2], &snapshot, &weight, &changes).unwrap();
+        assert_eq!(local.storage.get_last_hash(), snapshot.block.hash);
+        assert_eq!(StateOverlay::new(&*local.storage).state_root(), snapshot.block.header.state_root);
+    }
+
+    #[tokio::test]
+    async fn tampered_snapshot_state_is_rejected() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let b = test_node(&spec, MINER_B).await;
+        b.mine_block(true).await.unwrap();
+        let mut snapshot = StateSnapshot::capture(&*b.storage).unwrap();
+        b.mine_block(true).await.unwrap();
+        let headers = b.storage.get_headers(0, 10);
+
+        let (key, _) = snapshot.entries.iter().next().map(|(k, v)| (k.clone(), v.clone())).unwrap();
+        snapshot.entries.remove(&key);
+        let result = snapshot.verify(&headers, &spec.snn);
+        assert!(matches!(result, Err(SnapshotError::StateRootMismatch { .. })));
+    }
+}
@@ -0,0 +1,340 @@
+// src/core/state.rs
+use crate::constants::STATE_BUCKETS;
+use crate::core::block::Block;
+use crate::core::governance::Proposal;
+use crate::core::storage::ChainStore;
+use crate::core::transaction::{Transaction, TxError, TxKind};
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::collections::{BTreeMap, HashSet};
+
+/// Trạng thái một tài khoản: số dư và nonce kế tiếp được chấp nhận
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Account {
+    pub balance: u64,
+    pub nonce: u64,
+}
+
+/// Một mục trong kho tri thức của AI, ghi bởi giao dịch KnowledgeFact
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Fact {
+    pub value: String,
+    pub author: String,
+}
+
+/// Mọi giá trị trạng thái on-chain, lưu theo key trong Storage
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum StateValue {
+    Account(Account),
+    Proposal(Proposal),
+    Vote(bool),
+    Fact(Fact),
+    Counter(u64),
+}
+
+// --- State Keys ---
+// Các tiền tố thuộc trạng thái (tính vào state_root)
+pub const STATE_PREFIXES: [&str; 5] = ["acct:", "gov:", "fact:", "reward:", "supply:"];
+pub const NEXT_PROPOSAL_KEY: &str = "gov:next_proposal";
+pub const TOTAL_SUPPLY_KEY: &str = "supply:total";
+
+pub fn account_key(address: &str) -> String {
+    format!("acct:{}", address)
+}
+
+pub fn proposal_key(id: u64) -> String {
+    format!("gov:proposal:{}", id)
+}
+
+pub fn vote_key(proposal_id: u64, voter: &str) -> String {
+    format!("gov:vote:{}:{}", proposal_id, voter)
+}
+
+pub fn fact_key(key: &str) -> String {
+    format!("fact:{}", key)
+}
+
+/// Tổng thưởng một worker đã nhận
+pub fn reward_key(worker: &str) -> String {
+    format!("reward:{}", worker)
+}
+
+/// Thay đổi trạng thái của block (None = xóa key)
+pub type StateChanges = BTreeMap<String, Option<StateValue>>;
+
+/// Giá trị cũ của các key bị block thay đổi (None = trước đó chưa tồn tại)
+pub type UndoLog = Vec<(String, Option<StateValue>)>;
+
+/// Ghi UndoLog của một block: mỗi key chỉ lưu giá trị trước lần ghi đầu tiên
+#[derive(Default)]
+pub struct UndoRecorder {
+    log: UndoLog,
+    recorded: HashSet<String>,
+}
+
+impl UndoRecorder {
+    pub fn into_log(self) -> UndoLog {
+        self.log
+    }
+}
+
+/// Lớp trạng thái tạm trên nền Storage: mọi thay đổi nằm trong RAM
+/// cho tới khi được ghi cùng block trong một batch duy nhất.
+pub struct StateOverlay<'a, S: ChainStore> {
+    storage: &'a S,
+    entries: StateChanges,
+}
+
+impl<'a, S: ChainStore> StateOverlay<'a, S> {
+    pub fn new(storage: &'a S) -> Self {
+        Self { storage, entries: BTreeMap::new() }
+    }
+
+    fn lookup(&self, key: &str) -> Option<StateValue> {
+        match self.entries.get(key) {
+            Some(value) => value.clone(),
+            None => self.storage.get_state(key),
+        }
+    }
+
+    fn write(&mut self, key: String, value: StateValue, undo: &mut UndoRecorder) {
+        if undo.recorded.insert(key.clone()) {
+            undo.log.push((key.clone(), self.lookup(&key)));
+        }
+        self.entries.insert(key, Some(value));
+    }
+
+    pub fn account(&self, address: &str) -> Account {
+        match self.lookup(&account_key(address)) {
+            Some(StateValue::Account(acc)) => acc,
+            _ => Account::default(),
+        }
+    }
+
+    fn counter(&self, key: &str) -> u64 {
+        match self.lookup(key) {
+            Some(StateValue::Counter(n)) => n,
+            _ => 0,
+        }
+    }
+
+    fn proposal(&self, id: u64) -> Option<Proposal> {
+        match self.lookup(&proposal_key(id)) {
+            Some(StateValue::Proposal(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// Số dư của `address` sau khi cộng `amount`; lỗi nếu vượt u64 (không được làm mất coin)
+    fn credited_balance(&self, address: &str, amount: u64) -> Result<u64, TxError> {
+        self.account(address).balance.checked_add(amount)
+            .ok_or_else(|| TxError::BalanceOverflow(address.to_string()))
+    }
+
+    fn set_balance(&mut self, address: &str, balance: u64, undo: &mut UndoRecorder) {
+        let mut account = self.account(address);
+        account.balance = balance;
+        self.write(account_key(address), StateValue::Account(account), undo);
+    }
+
+    /// Áp dụng một giao dịch: kiểm tra nonce, số dư và luật riêng của từng loại,
+    /// sau đó mới ghi. Lỗi luôn xảy ra trước lần ghi đầu tiên nên overlay không bị dở dang.
+    pub fn apply_tx(&mut self, tx: &Transaction, undo: &mut UndoRecorder) -> Result<(), TxError> {
+        let sender = tx.sender_address().ok_or(TxError::InvalidSignature)?;
+        let mut from = self.account(&sender);
+        check_nonce(from.nonce, tx.nonce)?;
+
+        let required = tx.spend();
+        if from.balance < required {
+            return Err(TxError::InsufficientBalance { address: sender, balance: from.balance, required });
+        }
+
+        // Luật phụ thuộc trạng thái của từng loại giao dịch
+        let voted = match &tx.kind {
+            TxKind::Vote { proposal_id, approve } => {
+                let mut proposal = self.proposal(*proposal_id)
+                    .ok_or(TxError::UnknownProposal(*proposal_id))?;
+                if self.lookup(&vote_key(*proposal_id, &sender)).is_some() {
+                    return Err(TxError::AlreadyVoted { proposal_id: *proposal_id, voter: sender });
+                }
+                proposal.record_vote(*approve)?;
+                Some(proposal)
+            }
+            _ => None,
+        };
+        // Số dư mới của người nhận, tính trước khi ghi (chuyển cho chính mình: tính sau khi trừ)
+        let credited = match &tx.kind {
+            TxKind::Transfer { receiver: to, amount } | TxKind::WorkerReward { worker: to, amount } => {
+                let base = if *to == sender { from.balance - required } else { self.account(to).balance };
+                let balance = base.checked_add(*amount).ok_or_else(|| TxError::BalanceOverflow(to.clone()))?;
+                Some((to.clone(), balance))
+            }
+            _ => None,
+        };
+
+        from.balance -= required;
+        from.nonce += 1;
+        self.write(account_key(&sender), StateValue::Account(from), undo);
+
+        if let Some((to, balance)) = credited {
+            self.set_balance(&to, balance, undo);
+        }
+
+        match &tx.kind {
+            TxKind::Transfer { .. } => {}
+            TxKind::WorkerReward { worker, amount } => {
+                let total = self.counter(&reward_key(worker)).saturating_add(*amount);
+                self.write(reward_key(worker), StateValue::Counter(total), undo);
+            }
+            TxKind::Proposal { title, description } => {
+                let id = self.counter(NEXT_PROPOSAL_KEY).max(1);
+                let proposal = Proposal::new(id, title.clone(), description.clone(), sender);
+                self.write(proposal_key(id), StateValue::Proposal(proposal), undo);
+                self.write(NEXT_PROPOSAL_KEY.to_string(), StateValue::Counter(id + 1), undo);
+            }
+            TxKind::Vote { proposal_id, approve } => {
+                if let Some(proposal) = voted {
+                    self.write(proposal_key(*proposal_id), StateValue::Proposal(proposal), undo);
+                }
+                self.write(vote_key(*proposal_id, &sender), StateValue::Vote(*approve), undo);
+            }
+            TxKind::KnowledgeFact { key, value } => {
+                let fact = Fact { value: value.clone(), author: sender };
+                self.write(fact_key(key), StateValue::Fact(fact), undo);
+            }
+        }
+        Ok(())
+    }
+
+    /// Coinbase: phát hành `reward` coin mới và trả toàn bộ phí của block cho miner
+    pub fn apply_coinbase(&mut self, miner: &str, reward: u64, fees: u64, undo: &mut UndoRecorder) -> Result<(), TxError> {
+        let amount = reward.checked_add(fees).ok_or_else(|| TxError::BalanceOverflow(miner.to_string()))?;
+        let balance = self.credited_balance(miner, amount)?;
+        self.set_balance(miner, balance, undo);
+        let supply = self.counter(TOTAL_SUPPLY_KEY).saturating_add(reward);
+        self.write(TOTAL_SUPPLY_KEY.to_string(), StateValue::Counter(supply), undo);
+        Ok(())
+    }
+
+    /// Áp dụng toàn bộ giao dịch của block rồi coinbase, trả về UndoLog để rollback khi reorg
+    pub fn apply_block(&mut self, block: &Block, reward: u64) -> Result<UndoLog, (String, TxError)> {
+        let mut undo = UndoRecorder::default();
+        for tx in &block.transactions {
+            self.apply_tx(tx, &mut undo).map_err(|e| (tx.id.clone(), e))?;
+        }
+        self.apply_coinbase(&block.header.miner, reward, total_fees(&block.transactions), &mut undo)
+            .map_err(|e| ("coinbase".to_string(), e))?;
+        Ok(undo.into_log())
+    }
+
+    /// Khôi phục giá trị cũ từ UndoLog của một block bị gỡ khỏi chuỗi chính
+    pub fn revert(&mut self, undo: &UndoLog) {
+        for (key, previous) in undo.iter().rev() {
+            self.entries.insert(key.clone(), previous.clone());
+        }
+    }
+
+    /// Root trạng thái sau các thay đổi trong overlay: chỉ băm lại bucket có key bị thay đổi
+    pub fn state_root(&self) -> String {
+        let mut digests = self.storage.get_state_buckets();
+        let touched = rehash_buckets(
+            &self.entries,
+            |bucket| self.storage.get_bucket_keys(bucket),
+            |key| self.storage.get_state(key),
+        );
+        for (bucket, digest) in touched {
+            digests[bucket as usize] = digest;
+        }
+        root_from_buckets(&digests)
+    }
+
+    pub fn into_changes(self) -> StateChanges {
+        self.entries
+    }
+}
+
+/// Tổng phí của danh sách giao dịch (phí được trả cho miner qua coinbase)
+pub fn total_fees(txs: &[Transaction]) -> u64 {
+    txs.iter().fold(0u64, |acc, tx| acc.saturating_add(tx.fee))
+}
+
+/// Bucket của một key: 12 bit đầu của SHA256(key)
+pub fn state_bucket(key: &str) -> u16 {
+    let digest = Sha256::digest(key.as_bytes());
+    u16::from_be_bytes([digest[0], digest[1]]) >> 4
+}
+
+/// Digest một bucket: SHA256 trên các key/value (bincode) sắp theo key. Bucket rỗng = 0.
+pub fn bucket_digest<'a>(entries: impl IntoIterator<Item = (&'a String, &'a StateValue)>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut empty = true;
+    for (key, value) in entries {
+        let encoded = bincode::serialize(value).expect("Failed to encode state value");
+        hasher.update((key.len() as u64).to_le_bytes());
+        hasher.update(key.as_bytes());
+        hasher.update((encoded.len() as u64).to_le_bytes());
+        hasher.update(encoded);
+        empty = false;
+    }
+    if empty {
+        return [0u8; 32];
+    }
+    hasher.finalize().into()
+}
+
+/// Root trạng thái: SHA256 trên digest của STATE_BUCKETS bucket theo thứ tự
+pub fn root_from_buckets(digests: &[[u8; 32]]) -> String {
+    let mut hasher = Sha256::new();
+    for digest in digests {
+        hasher.update(digest);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Root của toàn bộ trạng thái (genesis, kiểm tra snapshot)
+pub fn state_root(entries: &BTreeMap<String, StateValue>) -> String {
+    let mut buckets: Vec<Vec<(&String, &StateValue)>> = vec![Vec::new(); STATE_BUCKETS];
+    for (key, value) in entries {
+        buckets[state_bucket(key) as usize].push((key, value));
+    }
+    let digests: Vec<[u8; 32]> = buckets.into_iter().map(bucket_digest).collect();
+    root_from_buckets(&digests)
+}
+
+/// Digest mới của các bucket bị `changes` chạm tới.
+/// `bucket_keys` và `lookup` đọc trạng thái nền (trước khi áp dụng `changes`).
+pub fn rehash_buckets(
+    changes: &StateChanges,
+    bucket_keys: impl Fn(u16) -> Vec<String>,
+    lookup: impl Fn(&str) -> Option<StateValue>,
+) -> BTreeMap<u16, [u8; 32]> {
+    let mut touched: BTreeMap<u16, Vec<(&String, &Option<StateValue>)>> = BTreeMap::new();
+    for (key, value) in changes {
+        touched.entry(state_bucket(key)).or_default().push((key, value));
+    }
+    touched.into_iter()
+        .map(|(bucket, bucket_changes)| {
+            let mut entries: BTreeMap<String, StateValue> = bucket_keys(bucket).into_iter()
+                .filter_map(|key| lookup(&key).map(|value| (key, value)))
+                .collect();
+            for (key, value) in bucket_changes {
+                match value {
+                    Some(v) => { entries.insert(key.clone(), v.clone()); }
+                    None => { entries.remove(key); }
+                }
+            }
+            (bucket, bucket_digest(&entries))
+        })
+        .collect()
+}
+
+/// Nonce phải đúng bằng giá trị kế tiếp: nhỏ hơn = dùng lại, lớn hơn = bị hở
+pub fn check_nonce(expected: u64, found: u64) -> Result<(), TxError> {
+    if found < expected {
+        return Err(TxError::NonceReused { expected, found });
+    }
+    if found > expected {
+        return Err(TxError::NonceGap { expected, found });
+    }
+    Ok(())
+}
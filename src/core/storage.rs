@@ -1,74 +1,250 @@
 // src/core/storage.rs
-use sled::{Db, IVec};
-use std::str::from_utf8;
-use serde::{Serialize, Deserialize};
-use crate::core::block::Block;
+// Giao diện lưu trữ chung của node. Backend: SledStore (ổ đĩa) và MemoryStore (RAM, dùng cho test nhiều node).
+use std::fmt;
+use crate::constants::LEGACY_FACT_AUTHOR;
+use crate::core::block::{Block, BlockHeader};
+use crate::core::fork_choice::ChainWeight;
+use crate::core::transaction::Transaction;
+use crate::core::governance::Proposal;
+use crate::core::snapshot::StateSnapshot;
+use crate::core::state::{
+    account_key, fact_key, proposal_key, Account, Fact, StateChanges, StateValue, UndoLog,
+    STATE_PREFIXES, TOTAL_SUPPLY_KEY,
+};
+use crate::ai::snn::SnnState;
+use std::collections::BTreeMap;
 
-pub struct Storage {
-    db: Db,
+/// Lỗi ghi/đọc Storage, trả về cho caller thay vì panic cả node
+#[derive(Debug)]
+pub enum StorageError {
+    Db(sled::Error),
+    Encode(String), // Không tuần tự hóa được dữ liệu trước khi ghi
+    UnsupportedSchema { found: u32, supported: u32 }, // DB do node mới hơn ghi
 }
 
-impl Storage {
-    pub fn new(path: &str) -> Self {
-        println!("💾 MOUNTING SLED DB AT: {}", path);
-        // Sled tự động tạo thư mục và file db
-        let db = sled::open(path).expect("Failed to open Sled database");
-        Self { db }
-    }
-
-    // --- Block Methods ---
-
-    pub fn save_block(&self, block: &Block) {
-        // Key: "block:<index>"
-        let key = format!("block:{}", block.index);
-        let value = serde_json::to_vec(block).expect("Failed to serialize block");
-        
-        self.db.insert(key.as_bytes(), value).unwrap();
-        
-        // Cập nhật chiều cao và hash mới nhất
-        self.db.insert("chain_height", &block.index.to_be_bytes()).unwrap();
-        self.db.insert("last_hash", block.hash.as_bytes()).unwrap();
-        
-        // Flush để đảm bảo dữ liệu ghi xuống ổ cứng
-        self.db.flush().unwrap();
-    }
-
-    pub fn get_block(&self, index: u64) -> Option<Block> {
-        let key = format!("block:{}", index);
-        if let Ok(Some(value)) = self.db.get(key.as_bytes()) {
-            return serde_json::from_slice(&value).ok();
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Db(e) => write!(f, "database error: {}", e),
+            StorageError::Encode(e) => write!(f, "encoding error: {}", e),
+            StorageError::UnsupportedSchema { found, supported } => write!(
+                f, "database schema v{} is newer than supported v{}, upgrade the node", found, supported
+            ),
         }
-        None
     }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<sled::Error> for StorageError {
+    fn from(e: sled::Error) -> Self {
+        StorageError::Db(e)
+    }
+}
+
+/// Các địa chỉ được đánh chỉ mục cho một giao dịch: người gửi và người nhận (nếu có)
+pub fn indexed_addresses(tx: &Transaction) -> Vec<String> {
+    let mut addresses: Vec<String> = tx.sender_address().into_iter().collect();
+    if let Some(recipient) = tx.kind.recipient() {
+        addresses.push(recipient.to_string());
+    }
+    addresses
+}
+
+/// Kho dữ liệu của chuỗi: block (kể cả nhánh phụ), đỉnh chuỗi chính, trạng thái on-chain
+/// (tài khoản, governance, fact) và chỉ mục giao dịch.
+/// Mỗi lệnh ghi phải nguyên tử: hoặc toàn bộ thay đổi được ghi, hoặc không gì cả.
+pub trait ChainStore: Send + Sync + 'static {
+    // --- Ghi ---
+
+    /// Ghi block nối tiếp đỉnh chuỗi chính hiện tại, kèm trạng thái SNN sau block,
+    /// thay đổi trạng thái và UndoLog của block.
+    fn save_block(
+        &self,
+        block: &Block,
+        snn_state: &SnnState,
+        changes: &StateChanges,
+        undo: &UndoLog,
+    ) -> Result<(), StorageError>;
+
+    /// Ghi block thuộc nhánh phụ (không thay đổi chuỗi chính)
+    fn save_side_block(&self, block: &Block, weight: &ChainWeight, snn_state: &SnnState) -> Result<(), StorageError>;
+
+    /// Chuyển chuỗi chính sang nhánh mới (kèm chỉ mục).
+    /// `undos[i]` là UndoLog của `enact[i]`, `changes` là trạng thái sau khi chuyển nhánh.
+    fn apply_reorg(
+        &self,
+        retract: &[Block],
+        enact: &[Block],
+        changes: &StateChanges,
+        undos: &[UndoLog],
+    ) -> Result<(), StorageError>;
+
+    /// Dựng lại toàn bộ chỉ mục giao dịch từ chuỗi chính, trả về số giao dịch đã đánh chỉ mục
+    fn reindex(&self) -> Result<u64, StorageError>;
+
+    /// Fast sync: ghi `headers` (#1..#h-1, không kèm thân block) và block của snapshot làm đỉnh chuỗi chính,
+    /// áp dụng `changes` để trạng thái khớp snapshot. Block trước snapshot không có UndoLog nên không reorg xuống dưới được;
+    /// chúng được coi như đã prune.
+    fn install_snapshot(
+        &self,
+        headers: &[BlockHeader],
+        snapshot: &StateSnapshot,
+        weight: &ChainWeight,
+        changes: &StateChanges,
+    ) -> Result<(), StorageError>;
+
+    /// Pruning: xóa thân block, trạng thái SNN, UndoLog và chỉ mục giao dịch của các block
+    /// chuỗi chính từ mốc prune hiện tại tới trước `below`. Header và con trỏ chuỗi chính được giữ lại.
+    /// Trả về số block đã xóa thân.
+    fn prune_blocks(&self, below: u64) -> Result<u64, StorageError>;
+
+    /// Các block chuỗi chính có index trong 1..pruned_height không còn thân block (0 = chưa prune).
+    /// Genesis luôn được giữ.
+    fn get_pruned_height(&self) -> u64;
+
+    fn is_pruned(&self, index: u64) -> bool {
+        index > 0 && index < self.get_pruned_height()
+    }
+
+    /// Lưu snapshot trạng thái mới nhất (thay snapshot cũ)
+    fn save_snapshot(&self, snapshot: &StateSnapshot) -> Result<(), StorageError>;
+    fn get_snapshot(&self) -> Option<StateSnapshot>;
+
+    // --- Block & đỉnh chuỗi ---
+
+    /// Hash của block tại `index` trên chuỗi chính
+    fn get_canonical_hash(&self, index: u64) -> Option<String>;
+    fn get_block_by_hash(&self, hash: &str) -> Option<Block>;
+    fn get_header(&self, hash: &str) -> Option<BlockHeader>;
+    /// Trọng lượng nhánh tính tới block `hash` (None nếu chưa biết block)
+    fn get_weight(&self, hash: &str) -> Option<ChainWeight>;
+    /// Trạng thái SNN đồng thuận ngay sau block `hash`
+    fn get_snn_state(&self, hash: &str) -> Option<SnnState>;
+    /// UndoLog của block `hash` (chỉ có với block từng nằm trên chuỗi chính)
+    fn get_undo(&self, hash: &str) -> Option<UndoLog>;
+    fn get_height(&self) -> u64;
+    fn get_last_hash(&self) -> String;
+
+    fn has_block(&self, hash: &str) -> bool {
+        self.get_block_by_hash(hash).is_some()
+    }
+
+    fn get_block(&self, index: u64) -> Option<Block> {
+        self.get_block_by_hash(&self.get_canonical_hash(index)?)
+    }
+
+    /// Tối đa `limit` block liên tiếp của chuỗi chính bắt đầu từ `from`
+    fn get_blocks(&self, from: u64, limit: u64) -> Vec<Block> {
+        (from..from.saturating_add(limit))
+            .map_while(|i| self.get_block(i))
+            .collect()
+    }
+
+    /// Tối đa `count` header liên tiếp của chuỗi chính bắt đầu từ `from` (không kèm thân block)
+    fn get_headers(&self, from: u64, count: u64) -> Vec<BlockHeader> {
+        (from..from.saturating_add(count))
+            .map_while(|i| self.get_canonical_hash(i).and_then(|h| self.get_header(&h)))
+            .collect()
+    }
+
+    // --- Chỉ mục giao dịch ---
+
+    /// Vị trí (height, vị trí trong block) của giao dịch trên chuỗi chính
+    fn get_tx_location(&self, id: &str) -> Option<(u64, u32)>;
 
-    pub fn get_height(&self) -> u64 {
-        if let Ok(Some(val)) = self.db.get("chain_height") {
-            let mut arr = [0u8; 8];
-            arr.copy_from_slice(&val);
-            return u64::from_be_bytes(arr);
+    /// Vị trí các giao dịch liên quan tới `address`, mới nhất trước
+    fn get_address_locations(&self, address: &str, limit: usize) -> Vec<(u64, u32)>;
+
+    /// Tìm giao dịch đã xác nhận qua chỉ mục: (block chứa nó, vị trí trong block)
+    fn find_transaction(&self, id: &str) -> Option<(Block, usize)> {
+        let (height, pos) = self.get_tx_location(id)?;
+        let block = self.get_block(height)?;
+        block.transactions.get(pos as usize)?;
+        Some((block, pos as usize))
+    }
+
+    /// Lịch sử giao dịch đã xác nhận liên quan tới `address` (mới nhất trước): (height, tx)
+    fn get_address_history(&self, address: &str, limit: usize) -> Vec<(u64, Transaction)> {
+        let mut cached: Option<Block> = None;
+        let mut history = Vec::new();
+        for (height, pos) in self.get_address_locations(address, limit) {
+            // Nhiều giao dịch liên tiếp thường nằm cùng một block
+            if cached.as_ref().map(|b| b.header.index) != Some(height) {
+                cached = self.get_block(height);
+            }
+            if let Some(tx) = cached.as_ref().and_then(|b| b.transactions.get(pos as usize)) {
+                history.push((height, tx.clone()));
+            }
         }
-        0 // Mặc định là 0 nếu chưa có block nào
+        history
     }
 
-    pub fn get_last_hash(&self) -> String {
-        if let Ok(Some(val)) = self.db.get("last_hash") {
-            return String::from_utf8(val.to_vec()).unwrap_or_else(|_| "0".repeat(64));
+    // --- On-chain State ---
+
+    fn get_state(&self, key: &str) -> Option<StateValue>;
+
+    /// Mọi mục trạng thái có key bắt đầu bằng `prefix`, sắp theo key
+    fn scan_state(&self, prefix: &str) -> Vec<(String, StateValue)>;
+
+    /// Digest từng bucket của trạng thái đã ghi (STATE_BUCKETS phần tử, bucket rỗng = 0)
+    fn get_state_buckets(&self) -> Vec<[u8; 32]>;
+
+    /// Các key trạng thái thuộc `bucket`
+    fn get_bucket_keys(&self, bucket: u16) -> Vec<String>;
+
+    /// Toàn bộ trạng thái on-chain (snapshot)
+    fn get_state_entries(&self) -> BTreeMap<String, StateValue> {
+        STATE_PREFIXES.iter()
+            .flat_map(|prefix| self.scan_state(prefix))
+            .collect()
+    }
+
+    fn get_account(&self, address: &str) -> Option<Account> {
+        match self.get_state(&account_key(address))? {
+            StateValue::Account(acc) => Some(acc),
+            _ => None,
+        }
+    }
+
+    /// Tổng cung: allocations của genesis + thưởng block đã phát hành
+    fn get_total_supply(&self) -> u64 {
+        match self.get_state(TOTAL_SUPPLY_KEY) {
+            Some(StateValue::Counter(n)) => n,
+            _ => 0,
         }
-        "0".repeat(64) // Genesis prev_hash mặc định
     }
 
-    // --- AI Knowledge Base (Key-Value) ---
+    fn get_proposal(&self, id: u64) -> Option<Proposal> {
+        match self.get_state(&proposal_key(id))? {
+            StateValue::Proposal(p) => Some(p),
+            _ => None,
+        }
+    }
 
-    pub fn learn_fact(&self, key: &str, value: &str) {
-        let db_key = format!("fact:{}", key);
-        self.db.insert(db_key.as_bytes(), value.as_bytes()).unwrap();
+    fn get_proposals(&self) -> Vec<Proposal> {
+        self.scan_state("gov:proposal:")
+            .into_iter()
+            .filter_map(|(_, v)| match v {
+                StateValue::Proposal(p) => Some(p),
+                _ => None,
+            })
+            .collect()
     }
 
-    pub fn recall_fact(&self, key: &str) -> Option<String> {
-        let db_key = format!("fact:{}", key);
-        if let Ok(Some(val)) = self.db.get(db_key.as_bytes()) {
-            return Some(from_utf8(&val).unwrap_or("").to_string());
+    // --- AI Knowledge Base (ghi qua giao dịch KnowledgeFact) ---
+
+    fn recall_fact(&self, key: &str) -> Option<Fact> {
+        match self.get_state(&fact_key(key)) {
+            Some(StateValue::Fact(fact)) => Some(fact),
+            Some(_) => None,
+            None => self.get_legacy_fact(key)
+                .map(|value| Fact { value, author: LEGACY_FACT_AUTHOR.to_string() }),
         }
+    }
+
+    /// Fact nhập lại từ DB v0 khi migrate: chỉ đọc, không thuộc trạng thái on-chain (không tính vào state_root)
+    fn get_legacy_fact(&self, _key: &str) -> Option<String> {
         None
     }
 }
@@ -1,16 +1,213 @@
 // src/core/transaction.rs
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
 use std::sync::{Arc, RwLock};
-use ed25519_dalek::{Verifier, VerifyingKey, Signature};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use ed25519_dalek::{VerifyingKey, Signature};
 use sha2::{Sha256, Digest};
+use crate::constants::{
+    MAX_FACT_KEY_LEN, MAX_FACT_VALUE_LEN, MAX_PROPOSAL_DESCRIPTION_LEN, MAX_PROPOSAL_TITLE_LEN,
+    MEMPOOL_MAX_TXS, MEMPOOL_RBF_BUMP_PERCENT, MEMPOOL_TX_TTL_SECS, TX_SIGNING_DOMAIN,
+};
+use crate::core::storage::ChainStore;
+use crate::core::wallet::{address_from_public_key, is_valid_address};
+
+/// Lý do một giao dịch bị từ chối (Mempool hoặc khi kiểm tra Block)
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxError {
+    InvalidSignature,
+    Duplicate(String),
+    InsufficientBalance { address: String, balance: u64, required: u64 },
+    NonceReused { expected: u64, found: u64 },
+    NonceGap { expected: u64, found: u64 },
+    WrongChain { expected: String, found: String },
+    ReplacementUnderpriced { required: u64, found: u64 },
+    PoolFull,
+    IdMismatch { claimed: String, computed: String },
+    NonCanonicalEncoding(&'static str), // Tên trường bị mã hóa sai
+    InvalidPayload(String),
+    UnknownProposal(u64),
+    ProposalClosed(u64),
+    AlreadyVoted { proposal_id: u64, voter: String },
+    BalanceOverflow(String), // Số dư của địa chỉ sẽ vượt u64
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxError::InvalidSignature => write!(f, "invalid signature"),
+            TxError::Duplicate(id) => write!(f, "transaction {} already exists", id),
+            TxError::InsufficientBalance { address, balance, required } => {
+                write!(f, "insufficient balance for {}: has {}, needs {}", address, balance, required)
+            }
+            TxError::NonceReused { expected, found } => {
+                write!(f, "nonce {} already used (expected {})", found, expected)
+            }
+            TxError::NonceGap { expected, found } => {
+                write!(f, "nonce gap: expected {}, found {}", expected, found)
+            }
+            TxError::WrongChain { expected, found } => {
+                write!(f, "wrong chain id: expected {}, found {}", expected, found)
+            }
+            TxError::ReplacementUnderpriced { required, found } => {
+                write!(f, "replacement fee too low: needs at least {}, found {}", required, found)
+            }
+            TxError::PoolFull => write!(f, "mempool is full and fee is too low to evict"),
+            TxError::IdMismatch { claimed, computed } => {
+                write!(f, "transaction id {} does not match payload hash {}", claimed, computed)
+            }
+            TxError::NonCanonicalEncoding(field) => {
+                write!(f, "{} must be lowercase hex of the exact length", field)
+            }
+            TxError::InvalidPayload(reason) => write!(f, "invalid payload: {}", reason),
+            TxError::UnknownProposal(id) => write!(f, "proposal {} not found", id),
+            TxError::ProposalClosed(id) => write!(f, "proposal {} is closed", id),
+            TxError::AlreadyVoted { proposal_id, voter } => {
+                write!(f, "{} already voted on proposal {}", voter, proposal_id)
+            }
+            TxError::BalanceOverflow(address) => write!(f, "balance of {} would overflow", address),
+        }
+    }
+}
+
+impl std::error::Error for TxError {}
+
+impl TxError {
+    /// Mã lỗi ổn định trả về cho client (không phụ thuộc câu chữ của Display)
+    pub fn code(&self) -> &'static str {
+        match self {
+            TxError::InvalidSignature => "invalid_signature",
+            TxError::Duplicate(_) => "duplicate",
+            TxError::InsufficientBalance { .. } => "insufficient_balance",
+            TxError::NonceReused { .. } => "nonce_reused",
+            TxError::NonceGap { .. } => "nonce_gap",
+            TxError::WrongChain { .. } => "wrong_chain",
+            TxError::ReplacementUnderpriced { .. } => "replacement_underpriced",
+            TxError::PoolFull => "pool_full",
+            TxError::IdMismatch { .. } => "id_mismatch",
+            TxError::NonCanonicalEncoding(_) => "non_canonical_encoding",
+            TxError::InvalidPayload(_) => "invalid_payload",
+            TxError::UnknownProposal(_) => "unknown_proposal",
+            TxError::ProposalClosed(_) => "proposal_closed",
+            TxError::AlreadyVoted { .. } => "already_voted",
+            TxError::BalanceOverflow(_) => "balance_overflow",
+        }
+    }
+
+    /// Giao dịch sai bất kể trạng thái local: chữ ký, mã hóa, chain id, payload.
+    /// Số dư, nonce, đề xuất DAO... phụ thuộc Mempool của từng node nên không tính là lỗi của peer.
+    pub fn is_peer_fault(&self) -> bool {
+        matches!(
+            self,
+            TxError::InvalidSignature
+                | TxError::WrongChain { .. }
+                | TxError::IdMismatch { .. }
+                | TxError::NonCanonicalEncoding(_)
+                | TxError::InvalidPayload(_)
+        )
+    }
+}
+
+/// Hex chuẩn: đúng độ dài, chỉ gồm chữ số và chữ thường
+fn is_canonical_hex(value: &str, bytes: usize) -> bool {
+    value.len() == bytes * 2 && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Loại giao dịch: mỗi loại là một chuyển trạng thái on-chain có ký & trả phí
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxKind {
+    Transfer { receiver: String, amount: u64 },
+    Proposal { title: String, description: String },
+    Vote { proposal_id: u64, approve: bool },
+    KnowledgeFact { key: String, value: String },
+    WorkerReward { worker: String, amount: u64 }, // Trả thưởng cho WebNode worker
+}
+
+impl TxKind {
+    /// Phần payload ký của từng loại. Chuỗi tự do được hex để không lẫn dấu phân cách.
+    pub fn signing_fields(&self) -> String {
+        match self {
+            TxKind::Transfer { receiver, amount } => format!("transfer:{}:{}", receiver, amount),
+            TxKind::Proposal { title, description } => {
+                format!("proposal:{}:{}", hex::encode(title), hex::encode(description))
+            }
+            TxKind::Vote { proposal_id, approve } => format!("vote:{}:{}", proposal_id, approve),
+            TxKind::KnowledgeFact { key, value } => {
+                format!("fact:{}:{}", hex::encode(key), hex::encode(value))
+            }
+            TxKind::WorkerReward { worker, amount } => format!("reward:{}:{}", worker, amount),
+        }
+    }
+
+    /// Số coin rời khỏi tài khoản người gửi (chưa tính phí)
+    pub fn value(&self) -> u64 {
+        match self {
+            TxKind::Transfer { amount, .. } | TxKind::WorkerReward { amount, .. } => *amount,
+            _ => 0,
+        }
+    }
+
+    /// Địa chỉ nhận coin (nếu có)
+    pub fn recipient(&self) -> Option<&str> {
+        match self {
+            TxKind::Transfer { receiver, .. } => Some(receiver),
+            TxKind::WorkerReward { worker, .. } => Some(worker),
+            _ => None,
+        }
+    }
+
+    /// Kiểm tra không cần trạng thái (độ dài, định dạng địa chỉ...)
+    pub fn check(&self, sender: &str) -> Result<(), TxError> {
+        let invalid = |reason: &str| Err(TxError::InvalidPayload(reason.to_string()));
+        match self {
+            TxKind::Transfer { receiver, .. } => {
+                if !is_valid_address(receiver) {
+                    return invalid("receiver is not a valid address");
+                }
+            }
+            TxKind::Proposal { title, description } => {
+                if title.trim().is_empty() || title.len() > MAX_PROPOSAL_TITLE_LEN {
+                    return invalid("proposal title is empty or too long");
+                }
+                if description.len() > MAX_PROPOSAL_DESCRIPTION_LEN {
+                    return invalid("proposal description is too long");
+                }
+            }
+            TxKind::Vote { .. } => {}
+            TxKind::KnowledgeFact { key, value } => {
+                if key.is_empty() || key.len() > MAX_FACT_KEY_LEN {
+                    return invalid("fact key is empty or too long");
+                }
+                if value.len() > MAX_FACT_VALUE_LEN {
+                    return invalid("fact value is too long");
+                }
+            }
+            TxKind::WorkerReward { worker, amount } => {
+                if !is_valid_address(worker) {
+                    return invalid("worker is not a valid address");
+                }
+                if worker == sender {
+                    return invalid("cannot reward yourself");
+                }
+                if *amount == 0 {
+                    return invalid("reward amount must be positive");
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: String,
+    pub chain_id: String, // Chống replay giữa các devnet/testnet
     pub sender: String,   // Hex Public Key
-    pub receiver: String, // Hex Address
-    pub amount: u64,
+    pub kind: TxKind,
     pub fee: u64,
     pub nonce: u64,
     pub timestamp: u64,
@@ -18,16 +215,57 @@ pub struct Transaction {
 }
 
 impl Transaction {
+    /// Payload được ký: <domain>|<chain_id>|sender:fee:nonce:timestamp|<kind fields>
+    pub fn signing_payload(&self) -> String {
+        format!(
+            "{}|{}|{}:{}:{}:{}|{}",
+            TX_SIGNING_DOMAIN, self.chain_id,
+            self.sender, self.fee, self.nonce, self.timestamp,
+            self.kind.signing_fields()
+        )
+    }
+
+    /// Tổng số coin người gửi phải có: giá trị chuyển đi + phí
+    pub fn spend(&self) -> u64 {
+        self.kind.value().saturating_add(self.fee)
+    }
+
     pub fn calculate_hash(&self) -> String {
-        let payload = format!(
-            "{}:{}:{}:{}:{}:{}",
-            self.sender, self.receiver, self.amount, self.fee, self.nonce, self.timestamp
-        );
         let mut hasher = Sha256::new();
-        hasher.update(payload);
+        hasher.update(self.signing_payload());
         hex::encode(hasher.finalize())
     }
 
+    pub fn check_chain(&self, chain_id: &str) -> Result<(), TxError> {
+        if self.chain_id != chain_id {
+            return Err(TxError::WrongChain { expected: chain_id.to_string(), found: self.chain_id.clone() });
+        }
+        Ok(())
+    }
+
+    /// Chống malleability: hex chuẩn cho sender/signature và id đúng bằng hash payload đã ký.
+    /// Nhờ vậy một giao dịch đã ký chỉ có đúng một id.
+    pub fn check_integrity(&self) -> Result<(), TxError> {
+        if !is_canonical_hex(&self.sender, 32) {
+            return Err(TxError::NonCanonicalEncoding("sender"));
+        }
+        if !is_canonical_hex(&self.signature, 64) {
+            return Err(TxError::NonCanonicalEncoding("signature"));
+        }
+        let computed = self.calculate_hash();
+        if self.id != computed {
+            return Err(TxError::IdMismatch { claimed: self.id.clone(), computed });
+        }
+        let sender = self.sender_address().ok_or(TxError::InvalidSignature)?;
+        self.kind.check(&sender)
+    }
+
+    /// Địa chỉ ví PAPPAP của người gửi (suy ra từ public key)
+    pub fn sender_address(&self) -> Option<String> {
+        let pub_bytes = hex::decode(&self.sender).ok()?;
+        Some(address_from_public_key(&pub_bytes))
+    }
+
     pub fn verify(&self) -> bool {
         // 1. Decode Sender (Public Key)
         let pub_bytes = match hex::decode(&self.sender) {
@@ -35,7 +273,12 @@ impl Transaction {
             Err(_) => return false,
         };
         
-        let pub_key = match VerifyingKey::from_bytes(pub_bytes.as_slice().try_into().unwrap()) {
+        // Public key sai độ dài (dữ liệu từ mạng) -> từ chối thay vì panic
+        let pub_arr: [u8; 32] = match pub_bytes.try_into() {
+            Ok(a) => a,
+            Err(_) => return false,
+        };
+        let pub_key = match VerifyingKey::from_bytes(&pub_arr) {
             Ok(k) => k,
             Err(_) => return false,
         };
@@ -51,62 +294,550 @@ impl Transaction {
         let sig_arr: [u8; 64] = sig_bytes.try_into().unwrap();
         let signature = Signature::from_bytes(&sig_arr);
 
-        // 3. Verify Payload
-        let payload = format!(
-            "{}:{}:{}:{}:{}:{}",
-            self.sender, self.receiver, self.amount, self.fee, self.nonce, self.timestamp
-        );
+        // 3. Verify Payload (có domain & chain_id).
+        // verify_strict từ chối chữ ký có S không chuẩn và public key bậc nhỏ.
+        pub_key.verify_strict(self.signing_payload().as_bytes(), &signature).is_ok()
+    }
 
-        pub_key.verify(payload.as_bytes(), &signature).is_ok()
+    /// Kích thước mã hóa nhị phân, dùng để tính phí theo byte
+    pub fn encoded_size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(u64::MAX).max(1)
     }
 }
 
 // --- MEMPOOL ---
-// Nơi chứa các giao dịch chờ được đóng gói vào Block
+// Nơi chứa các giao dịch chờ được đóng gói vào Block.
+// Mỗi người gửi có một hàng đợi theo nonce; giữa các người gửi ưu tiên phí theo byte.
+
+/// Phí trên mỗi byte, so sánh bằng nhân chéo để không mất chính xác
+#[derive(Clone, Copy, Debug)]
+struct FeeRate {
+    fee: u64,
+    size: u64,
+}
+
+impl FeeRate {
+    fn of(tx: &Transaction) -> Self {
+        Self { fee: tx.fee, size: tx.encoded_size() }
+    }
+
+    fn as_f64(&self) -> f64 {
+        self.fee as f64 / self.size as f64
+    }
+}
+
+impl Ord for FeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.fee as u128 * other.size as u128).cmp(&(other.fee as u128 * self.size as u128))
+    }
+}
+
+impl PartialOrd for FeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FeeRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FeeRate {}
+
+struct PoolEntry {
+    tx: Transaction,
+    rate: FeeRate,
+    received: Instant,
+}
+
+#[derive(Default)]
+struct PoolInner {
+    queues: HashMap<String, BTreeMap<u64, PoolEntry>>, // Địa chỉ -> (nonce -> tx)
+    ids: HashMap<String, (String, u64)>,               // tx id -> (địa chỉ, nonce)
+    bytes: u64,
+}
+
+impl PoolInner {
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn insert(&mut self, sender: String, entry: PoolEntry) -> Option<PoolEntry> {
+        let nonce = entry.tx.nonce;
+        let replaced = self.remove(&sender, nonce);
+        self.ids.insert(entry.tx.id.clone(), (sender.clone(), nonce));
+        self.bytes += entry.rate.size;
+        self.queues.entry(sender).or_default().insert(nonce, entry);
+        replaced
+    }
+
+    fn remove(&mut self, sender: &str, nonce: u64) -> Option<PoolEntry> {
+        let queue = self.queues.get_mut(sender)?;
+        let entry = queue.remove(&nonce)?;
+        if queue.is_empty() {
+            self.queues.remove(sender);
+        }
+        self.ids.remove(&entry.tx.id);
+        self.bytes -= entry.rate.size;
+        Some(entry)
+    }
+
+    /// Bỏ giao dịch ở `nonce` và mọi nonce sau nó (chúng không thể vào block nếu thiếu nonce này)
+    fn remove_from(&mut self, sender: &str, nonce: u64) -> usize {
+        let doomed: Vec<u64> = match self.queues.get(sender) {
+            Some(queue) => queue.range(nonce..).map(|(n, _)| *n).collect(),
+            None => return 0,
+        };
+        for n in &doomed {
+            self.remove(sender, *n);
+        }
+        doomed.len()
+    }
+
+    /// Bỏ các nonce đã được dùng trên chuỗi (tx khác cùng nonce đã vào block)
+    fn remove_stale(&mut self, sender: &str, account_nonce: u64) {
+        let stale: Vec<u64> = match self.queues.get(sender) {
+            Some(queue) => queue.range(..account_nonce).map(|(n, _)| *n).collect(),
+            None => return,
+        };
+        for n in stale {
+            self.remove(sender, n);
+        }
+    }
+
+    fn expire(&mut self, ttl: Duration) -> usize {
+        let now = Instant::now();
+        let expired: Vec<(String, u64)> = self.queues.iter()
+            .filter_map(|(sender, queue)| {
+                queue.values()
+                    .find(|e| now.duration_since(e.received) > ttl)
+                    .map(|e| (sender.clone(), e.tx.nonce))
+            })
+            .collect();
+        expired.iter().map(|(sender, nonce)| self.remove_from(sender, *nonce)).sum()
+    }
+
+    /// Ứng viên bị đẩy ra khi đầy: giao dịch cuối hàng đợi có phí/byte thấp nhất
+    fn cheapest_tail(&self) -> Option<(String, u64, FeeRate)> {
+        self.queues.iter()
+            .filter_map(|(sender, queue)| {
+                queue.values().next_back().map(|e| (sender.clone(), e.tx.nonce, e.rate))
+            })
+            .min_by(|a, b| a.2.cmp(&b.2))
+    }
+}
+
+/// Thống kê Mempool cho API
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MempoolStats {
+    pub size: usize,
+    pub bytes: u64,
+    pub min_fee: u64,
+    pub max_fee: u64,
+    pub min_fee_per_byte: f64,
+    pub max_fee_per_byte: f64,
+}
+
 #[derive(Clone)]
-pub struct Mempool {
-    pub pending: Arc<RwLock<HashMap<String, Transaction>>>,
+pub struct Mempool<S: ChainStore> {
+    inner: Arc<RwLock<PoolInner>>,
+    notify: Arc<Notify>, // Đánh thức Miner khi có tx sẵn sàng để đào
+    storage: Arc<S>, // Đọc số dư & nonce của tài khoản
+    chain_id: String,
+    max_txs: usize,
+    ttl: Duration,
 }
 
-impl Mempool {
-    pub fn new() -> Self {
+impl<S: ChainStore> Mempool<S> {
+    pub fn new(storage: Arc<S>, chain_id: String) -> Self {
         Self { 
-            pending: Arc::new(RwLock::new(HashMap::new())) 
+            inner: Arc::new(RwLock::new(PoolInner::default())),
+            notify: Arc::new(Notify::new()),
+            storage,
+            chain_id,
+            max_txs: MEMPOOL_MAX_TXS,
+            ttl: Duration::from_secs(MEMPOOL_TX_TTL_SECS),
         }
     }
 
-    pub fn add_tx(&self, tx: Transaction) -> bool {
+    pub fn add_tx(&self, tx: Transaction) -> Result<(), TxError> {
+        tx.check_chain(&self.chain_id)?;
+        tx.check_integrity()?;
+
         // Verify ngay tại cửa ngõ
         if !tx.verify() {
             println!("⚠️ Invalid Transaction Signature: {}", tx.id);
-            return false;
+            return Err(TxError::InvalidSignature);
         }
+        let sender = tx.sender_address().ok_or(TxError::InvalidSignature)?;
 
-        let mut pool = self.pending.write().unwrap();
-        if pool.contains_key(&tx.id) {
-            return false;
+        let mut pool = self.inner.write().unwrap();
+        let expired = pool.expire(self.ttl);
+        if expired > 0 {
+            println!("⌛ Mempool expired {} TXs", expired);
         }
-        
+        if pool.ids.contains_key(&tx.id) {
+            return Err(TxError::Duplicate(tx.id.clone()));
+        }
+
+        let account = self.storage.get_account(&sender).unwrap_or_default();
+        pool.remove_stale(&sender, account.nonce);
+        let rate = FeeRate::of(&tx);
+
+        // Nonce đã dùng trên chuỗi thì từ chối. Nonce phía trước được nhận dù hàng đợi đang hở:
+        // sau reorg, tx bị gỡ khỏi block quay lại Mempool khi nonce sau của nó vẫn đang chờ.
+        if tx.nonce < account.nonce {
+            return Err(TxError::NonceReused { expected: account.nonce, found: tx.nonce });
+        }
+
+        // Cùng người gửi & nonce: chỉ thay thế khi phí tăng đủ mức (replace-by-fee)
+        let queue = pool.queues.get(&sender);
+        let replacing = queue.and_then(|q| q.get(&tx.nonce));
+        if let Some(old) = replacing {
+            let bump = old.tx.fee.saturating_mul(MEMPOOL_RBF_BUMP_PERCENT) / 100;
+            let required = old.tx.fee.saturating_add(bump.max(1));
+            if tx.fee < required {
+                return Err(TxError::ReplacementUnderpriced { required, found: tx.fee });
+            }
+        }
+
+        // Tính cả các giao dịch đang chờ của cùng người gửi (trừ tx bị thay thế)
+        let spent: u64 = queue.map_or(0, |q| {
+            q.values()
+                .filter(|e| e.tx.nonce != tx.nonce)
+                .map(|e| e.tx.spend())
+                .sum()
+        });
+        let required = spent.saturating_add(tx.spend());
+        if account.balance < required {
+            return Err(TxError::InsufficientBalance { address: sender, balance: account.balance, required });
+        }
+
+        // Đầy: đẩy giao dịch rẻ nhất ra nếu tx mới trả phí/byte cao hơn
+        if replacing.is_none() && pool.len() >= self.max_txs {
+            match pool.cheapest_tail() {
+                Some((victim, nonce, cheapest)) if rate > cheapest => {
+                    pool.remove(&victim, nonce);
+                    println!("🧹 Mempool full, evicted TX of {} (nonce {})", victim, nonce);
+                }
+                _ => return Err(TxError::PoolFull),
+            }
+        }
+
         println!("📥 Mempool received TX: {}", tx.id);
-        pool.insert(tx.id.clone(), tx);
-        true
+        let entry = PoolEntry { tx, rate, received: Instant::now() };
+        if let Some(old) = pool.insert(sender.clone(), entry) {
+            println!("🔁 Replaced TX {} by fee", old.tx.id);
+        }
+        // Tx phía sau một lỗ nonce chưa đào được: không đánh thức Miner
+        let ready = pool.queues.get(&sender).is_some_and(|q| q.contains_key(&account.nonce));
+        drop(pool);
+        if ready {
+            self.notify.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Chờ tới khi có giao dịch sẵn sàng để đào (không tốn CPU như polling)
+    pub async fn wait_for_tx(&self) {
+        self.notify.notified().await;
     }
 
-    /// Lấy n giao dịch để Miner đóng gói
-    pub fn pop_n(&self, n: usize) -> Vec<Transaction> {
-        let mut pool = self.pending.write().unwrap();
-        let keys: Vec<String> = pool.keys().take(n).cloned().collect();
+    /// Bỏ tx có nonce đã dùng, trả về nonce kế tiếp của từng người gửi đang có tx chờ
+    fn next_nonces(&self, pool: &mut PoolInner) -> HashMap<String, u64> {
+        let senders: Vec<String> = pool.queues.keys().cloned().collect();
+        let mut next_nonce = HashMap::new();
+        for sender in senders {
+            let account = self.storage.get_account(&sender).unwrap_or_default();
+            pool.remove_stale(&sender, account.nonce);
+            next_nonce.insert(sender, account.nonce);
+        }
+        next_nonce
+    }
+
+    fn any_ready(pool: &PoolInner, next_nonce: &HashMap<String, u64>) -> bool {
+        pool.queues.iter().any(|(sender, queue)| next_nonce.get(sender).is_some_and(|n| queue.contains_key(n)))
+    }
+
+    /// Có ít nhất một tx "sẵn sàng" (nonce đúng bằng nonce kế tiếp của tài khoản).
+    /// Tx đứng sau lỗ nonce (vd. sau reorg) không tính là đang chờ đào.
+    pub fn has_ready(&self) -> bool {
+        let mut pool = self.inner.write().unwrap();
+        let next_nonce = self.next_nonces(&mut pool);
+        Self::any_ready(&pool, &next_nonce)
+    }
+
+    /// Chọn giao dịch để Miner đóng gói (không xóa khỏi Mempool), tối đa `max_txs` tx và `max_bytes` byte.
+    /// Giữa các người gửi: phí/byte cao trước; trong một người gửi: đúng thứ tự nonce,
+    /// chỉ lấy đoạn nonce liên tục từ nonce của tài khoản (tx sau lỗ hở nằm chờ).
+    pub fn select(&self, max_txs: usize, max_bytes: u64) -> Vec<Transaction> {
+        let mut pool = self.inner.write().unwrap();
+        pool.expire(self.ttl);
+        let mut next_nonce = self.next_nonces(&mut pool);
+
+        // Heap chỉ chứa tx "sẵn sàng": nonce đúng bằng nonce kế tiếp của tài khoản
+        let mut heap = BinaryHeap::new();
+        for (sender, queue) in &pool.queues {
+            if let Some(entry) = queue.get(&next_nonce[sender]) {
+                heap.push((entry.rate, std::cmp::Reverse(entry.tx.id.clone()), sender.clone()));
+            }
+        }
+
         let mut txs = Vec::new();
-        
-        for key in keys {
-            if let Some(tx) = pool.remove(&key) {
-                txs.push(tx);
+        let mut bytes = 0u64;
+        while txs.len() < max_txs {
+            let Some((_, _, sender)) = heap.pop() else { break };
+            let queue = &pool.queues[&sender];
+            let nonce = next_nonce[&sender];
+            let entry = &queue[&nonce];
+
+            // Không vừa block: bỏ qua người gửi này (các nonce sau cũng không thể vào)
+            if bytes + entry.rate.size > max_bytes {
+                continue;
+            }
+            bytes += entry.rate.size;
+            txs.push(entry.tx.clone());
+
+            let following = nonce + 1;
+            next_nonce.insert(sender.clone(), following);
+            if let Some(entry) = queue.get(&following) {
+                heap.push((entry.rate, std::cmp::Reverse(entry.tx.id.clone()), sender));
             }
         }
         txs
     }
     
+    /// Loại bỏ các giao dịch đã nằm trong Block (tự đào hoặc nhận từ mạng).
+    /// Block có thể lấp lỗ nonce khiến tx đang chờ trở nên sẵn sàng: khi đó đánh thức Miner.
+    pub fn remove_included(&self, txs: &[Transaction]) {
+        let mut pool = self.inner.write().unwrap();
+        for tx in txs {
+            if let Some((sender, nonce)) = pool.ids.get(&tx.id).cloned() {
+                pool.remove(&sender, nonce);
+            }
+        }
+        let next_nonce = self.next_nonces(&mut pool);
+        let ready = Self::any_ready(&pool, &next_nonce);
+        drop(pool);
+        if ready {
+            self.notify.notify_one();
+        }
+    }
+
+    /// Loại bỏ giao dịch không còn hợp lệ cùng các nonce phía sau của người gửi
+    pub fn reject(&self, tx: &Transaction) {
+        let mut pool = self.inner.write().unwrap();
+        if let Some((sender, nonce)) = pool.ids.get(&tx.id).cloned() {
+            pool.remove_from(&sender, nonce);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Transaction> {
+        let pool = self.inner.read().unwrap();
+        let (sender, nonce) = pool.ids.get(id)?;
+        Some(pool.queues[sender][nonce].tx.clone())
+    }
+
+    /// Giao dịch đang chờ của một địa chỉ, theo thứ tự nonce
+    pub fn pending_for(&self, address: &str) -> Vec<Transaction> {
+        let pool = self.inner.read().unwrap();
+        pool.queues.get(address)
+            .map(|q| q.values().map(|e| e.tx.clone()).collect())
+            .unwrap_or_default()
+    }
+
     pub fn size(&self) -> usize {
-        self.pending.read().unwrap().len()
+        self.inner.read().unwrap().len()
+    }
+
+    pub fn stats(&self) -> MempoolStats {
+        let pool = self.inner.read().unwrap();
+        let entries = || pool.queues.values().flat_map(|q| q.values());
+        let (Some(min_rate), Some(max_rate)) = (entries().map(|e| e.rate).min(), entries().map(|e| e.rate).max()) else {
+            return MempoolStats::default();
+        };
+        MempoolStats {
+            size: pool.len(),
+            bytes: pool.bytes,
+            min_fee: entries().map(|e| e.tx.fee).min().unwrap_or(0),
+            max_fee: entries().map(|e| e.tx.fee).max().unwrap_or(0),
+            min_fee_per_byte: min_rate.as_f64(),
+            max_fee_per_byte: max_rate.as_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::core::chain_spec::{ensure_genesis, ChainSpec};
+    use crate::core::memory_store::MemoryStore;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    pub(crate) const TEST_CHAIN: &str = "pappap-test";
+
+    /// Khóa cố định theo `seed` để test tất định
+    pub(crate) fn test_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    pub(crate) fn address_of(key: &SigningKey) -> String {
+        address_from_public_key(&key.verifying_key().to_bytes())
+    }
+
+    pub(crate) fn signed_tx(key: &SigningKey, kind: TxKind, fee: u64, nonce: u64) -> Transaction {
+        let mut tx = Transaction {
+            id: String::new(),
+            chain_id: TEST_CHAIN.to_string(),
+            sender: hex::encode(key.verifying_key().to_bytes()),
+            kind,
+            fee,
+            nonce,
+            timestamp: 1_700_000_000,
+            signature: String::new(),
+        };
+        tx.id = tx.calculate_hash();
+        tx.signature = hex::encode(key.sign(tx.signing_payload().as_bytes()).to_bytes());
+        tx
+    }
+
+    pub(crate) fn transfer(key: &SigningKey, amount: u64, fee: u64, nonce: u64) -> Transaction {
+        let receiver = address_of(&test_key(0xEE));
+        signed_tx(key, TxKind::Transfer { receiver, amount }, fee, nonce)
+    }
+
+    /// Spec test: mỗi khóa trong `funded` có `balance` coin ở genesis
+    pub(crate) fn test_spec(funded: &[&SigningKey], balance: u64) -> ChainSpec {
+        ChainSpec {
+            chain_id: TEST_CHAIN.to_string(),
+            allocations: funded.iter().map(|key| (address_of(key), balance)).collect(),
+            ..ChainSpec::default()
+        }
+    }
+
+    fn pool_with(funded: &[&SigningKey], balance: u64) -> Mempool<MemoryStore> {
+        let storage = Arc::new(MemoryStore::default());
+        ensure_genesis(&*storage, &test_spec(funded, balance)).unwrap();
+        Mempool::new(storage, TEST_CHAIN.to_string())
+    }
+
+    fn ids(txs: &[Transaction]) -> Vec<String> {
+        txs.iter().map(|tx| tx.id.clone()).collect()
+    }
+
+    #[test]
+    fn select_orders_by_fee_rate_across_senders_and_nonce_within_sender() {
+        let (a, b) = (test_key(1), test_key(2));
+        let pool = pool_with(&[&a, &b], 1_000_000);
+        let a0 = transfer(&a, 1, 5, 0);
+        let a1 = transfer(&a, 1, 500, 1);
+        let b0 = transfer(&b, 1, 100, 0);
+        for tx in [&a0, &a1, &b0] {
+            pool.add_tx(tx.clone()).unwrap();
+        }
+
+        // a1 trả phí cao nhất nhưng phải đứng sau a0
+        assert_eq!(ids(&pool.select(10, u64::MAX)), ids(&[b0.clone(), a0.clone(), a1]));
+        assert_eq!(ids(&pool.select(2, u64::MAX)), ids(&[b0.clone(), a0]));
+        assert_eq!(ids(&pool.select(10, b0.encoded_size())), ids(&[b0]));
+        // select không xóa khỏi Mempool
+        assert_eq!(pool.size(), 3);
+    }
+
+    #[test]
+    fn accepts_future_nonces_but_selects_only_the_contiguous_run() {
+        let a = test_key(1);
+        let pool = pool_with(&[&a], 1_000_000);
+        let later = transfer(&a, 1, 10, 2);
+        pool.add_tx(later.clone()).unwrap();
+        assert!(!pool.has_ready());
+        assert!(pool.select(10, u64::MAX).is_empty());
+
+        let first = transfer(&a, 1, 10, 0);
+        pool.add_tx(first.clone()).unwrap();
+        assert!(pool.has_ready());
+        assert_eq!(ids(&pool.select(10, u64::MAX)), ids(std::slice::from_ref(&first)));
+
+        // Lấp lỗ hở: cả đoạn 0..=2 được chọn theo thứ tự nonce
+        let middle = transfer(&a, 1, 10, 1);
+        pool.add_tx(middle.clone()).unwrap();
+        assert_eq!(ids(&pool.select(10, u64::MAX)), ids(&[first, middle, later]));
+    }
+
+    #[test]
+    fn rejects_duplicates_and_overspending() {
+        let a = test_key(1);
+        let pool = pool_with(&[&a], 1_000);
+        pool.add_tx(transfer(&a, 600, 10, 0)).unwrap();
+        // Tính cả tx đang chờ của cùng người gửi: 610 + 410 > 1000
+        assert_eq!(
+            pool.add_tx(transfer(&a, 400, 10, 1)),
+            Err(TxError::InsufficientBalance { address: address_of(&a), balance: 1_000, required: 1_020 })
+        );
+        assert_eq!(pool.add_tx(transfer(&a, 600, 10, 0)), Err(TxError::Duplicate(transfer(&a, 600, 10, 0).id)));
+    }
+
+    #[test]
+    fn replace_by_fee_requires_bump() {
+        let a = test_key(1);
+        let pool = pool_with(&[&a], 1_000_000);
+        let original = transfer(&a, 1, 100, 0);
+        pool.add_tx(original.clone()).unwrap();
+
+        let underpriced = transfer(&a, 2, 109, 0);
+        assert_eq!(pool.add_tx(underpriced), Err(TxError::ReplacementUnderpriced { required: 110, found: 109 }));
+
+        let replacement = transfer(&a, 2, 110, 0);
+        pool.add_tx(replacement.clone()).unwrap();
+        assert_eq!(pool.size(), 1);
+        assert!(pool.get(&original.id).is_none());
+        assert!(pool.get(&replacement.id).is_some());
+    }
+
+    #[test]
+    fn full_pool_evicts_cheapest_tail_only_for_better_fee() {
+        let (a, b, c) = (test_key(1), test_key(2), test_key(3));
+        let mut pool = pool_with(&[&a, &b, &c], 1_000_000);
+        pool.max_txs = 2;
+        let a0 = transfer(&a, 1, 10, 0);
+        let b0 = transfer(&b, 1, 20, 0);
+        pool.add_tx(a0.clone()).unwrap();
+        pool.add_tx(b0.clone()).unwrap();
+
+        assert_eq!(pool.add_tx(transfer(&c, 1, 5, 0)), Err(TxError::PoolFull));
+
+        let c0 = transfer(&c, 1, 50, 0);
+        pool.add_tx(c0.clone()).unwrap();
+        assert_eq!(pool.size(), 2);
+        assert!(pool.get(&a0.id).is_none());
+        assert!(pool.get(&b0.id).is_some() && pool.get(&c0.id).is_some());
+    }
+
+    #[test]
+    fn expired_txs_are_dropped_with_their_successors() {
+        let a = test_key(1);
+        let mut pool = pool_with(&[&a], 1_000_000);
+        pool.ttl = Duration::from_millis(20);
+        pool.add_tx(transfer(&a, 1, 10, 0)).unwrap();
+        pool.add_tx(transfer(&a, 1, 10, 1)).unwrap();
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(pool.select(10, u64::MAX).is_empty());
+        assert_eq!(pool.size(), 0);
+        assert!(!pool.has_ready());
+    }
+
+    #[test]
+    fn reject_drops_tx_and_later_nonces() {
+        let a = test_key(1);
+        let pool = pool_with(&[&a], 1_000_000);
+        let txs: Vec<Transaction> = (0..3).map(|nonce| transfer(&a, 1, 10, nonce)).collect();
+        for tx in &txs {
+            pool.add_tx(tx.clone()).unwrap();
+        }
+        pool.reject(&txs[1]);
+        assert_eq!(ids(&pool.pending_for(&address_of(&a))), ids(&txs[..1]));
     }
 }
@@ -0,0 +1,234 @@
+// src/core/validation.rs
+use crate::constants::{ETERNAL_SIGNATURE, MAX_BLOCK_FUTURE_DRIFT};
+use crate::core::chain_spec::ChainSpec;
+use crate::core::block::Block;
+use crate::core::merkle::merkle_root;
+use crate::core::storage::{ChainStore, StorageError};
+use crate::core::state::{StateOverlay, UndoLog};
+use crate::core::transaction::TxError;
+use crate::core::wallet::is_valid_address;
+use crate::ai::snn::{DNum, SnnState};
+use crate::ai::snn_core::consensus_snn;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lý do một Block bị từ chối
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockError {
+    AlreadyKnown(String),
+    UnknownParent(String),
+    NotExtendingHead { head: String, parent: String },
+    HeightMismatch { expected: u64, found: u64 },
+    HashMismatch { claimed: String, computed: String },
+    InvalidTransaction(String),
+    TxIdMismatch { claimed: String, computed: String },
+    TxRootMismatch { claimed: String, computed: String },
+    RejectedTransaction { id: String, reason: TxError },
+    MissingUndo(String),
+    InvalidEternalSignature,
+    InvalidMiner(String),
+    BlockTooLarge { txs: usize, bytes: u64 },
+    ForbiddenGene(u64),
+    GeneCheckMissing,
+    TimestampBeforeParent { parent: u64, found: u64 },
+    TimestampInFuture { now: u64, found: u64 },
+    MissingSnnState(String),
+    SnnRootMismatch { expected: String, found: String },
+    SpikeScoreMismatch { expected: DNum, found: DNum },
+    StateRootMismatch { claimed: String, computed: String },
+    Storage(String), // Block hợp lệ nhưng không ghi được xuống DB
+}
+
+/// Kết quả thực thi block đã qua kiểm tra, dùng để ghi xuống Storage
+pub struct BlockEffects {
+    pub snn_state: SnnState,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::AlreadyKnown(h) => write!(f, "block {} already known", h),
+            BlockError::UnknownParent(h) => write!(f, "unknown parent block {}", h),
+            BlockError::NotExtendingHead { head, parent } => {
+                write!(f, "parent {} is not the current head {}", parent, head)
+            }
+            BlockError::HeightMismatch { expected, found } => {
+                write!(f, "height mismatch: expected {}, found {}", expected, found)
+            }
+            BlockError::HashMismatch { claimed, computed } => {
+                write!(f, "hash mismatch: claimed {}, computed {}", claimed, computed)
+            }
+            BlockError::InvalidTransaction(id) => write!(f, "invalid transaction {}", id),
+            BlockError::TxIdMismatch { claimed, computed } => {
+                write!(f, "tx id mismatch: claimed {}, computed {}", claimed, computed)
+            }
+            BlockError::TxRootMismatch { claimed, computed } => {
+                write!(f, "tx_root mismatch: claimed {}, computed {}", claimed, computed)
+            }
+            BlockError::RejectedTransaction { id, reason } => {
+                write!(f, "transaction {} rejected: {}", id, reason)
+            }
+            BlockError::StateRootMismatch { claimed, computed } => {
+                write!(f, "state_root mismatch: claimed {}, computed {}", claimed, computed)
+            }
+            BlockError::MissingUndo(h) => write!(f, "no undo log for block {}", h),
+            BlockError::InvalidEternalSignature => write!(f, "invalid eternal signature"),
+            BlockError::InvalidMiner(miner) => write!(f, "miner {} is not a valid address", miner),
+            BlockError::BlockTooLarge { txs, bytes } => {
+                write!(f, "block too large: {} txs, {} bytes", txs, bytes)
+            }
+            BlockError::ForbiddenGene(index) => write!(f, "forbidden gene {}", index),
+            BlockError::GeneCheckMissing => write!(f, "forbidden gene check not performed"),
+            BlockError::TimestampBeforeParent { parent, found } => {
+                write!(f, "timestamp {} is before parent timestamp {}", found, parent)
+            }
+            BlockError::TimestampInFuture { now, found } => {
+                write!(f, "timestamp {} is too far in the future (now {})", found, now)
+            }
+            BlockError::MissingSnnState(h) => write!(f, "no SNN state for parent block {}", h),
+            BlockError::SnnRootMismatch { expected, found } => {
+                write!(f, "snn_root mismatch: expected {}, found {}", expected, found)
+            }
+            BlockError::SpikeScoreMismatch { expected, found } => {
+                write!(f, "spike_score does not reproduce: expected {}, found {}", expected, found)
+            }
+            BlockError::Storage(e) => write!(f, "storage failure: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+impl BlockError {
+    /// Block sai bất kể trạng thái local (peer gửi block này là có lỗi).
+    /// Lỗi phụ thuộc node nhận (chưa có block cha, lệch đồng hồ, lỗi DB...) thì không tính.
+    pub fn is_peer_fault(&self) -> bool {
+        !matches!(
+            self,
+            BlockError::AlreadyKnown(_)
+                | BlockError::UnknownParent(_)
+                | BlockError::NotExtendingHead { .. }
+                | BlockError::TimestampInFuture { .. }
+                | BlockError::MissingUndo(_)
+                | BlockError::MissingSnnState(_)
+                | BlockError::Storage(_)
+        )
+    }
+}
+
+impl From<StorageError> for BlockError {
+    fn from(e: StorageError) -> Self {
+        BlockError::Storage(e.to_string())
+    }
+}
+
+/// Kiểm tra toàn bộ một Block nhận từ mạng trước khi ghi vào Storage.
+/// Block được kiểm tra theo block cha của chính nó (có thể nằm trên nhánh phụ).
+/// Thứ tự kiểm tra: rẻ trước, đắt (chữ ký giao dịch) sau.
+pub fn validate_block<S: ChainStore>(block: &Block, storage: &S, spec: &ChainSpec) -> Result<BlockEffects, BlockError> {
+    if storage.has_block(&block.hash) {
+        return Err(BlockError::AlreadyKnown(block.hash.clone()));
+    }
+
+    // 1. Liên kết với block cha (mọi chuỗi đều bắt đầu từ genesis của chain spec)
+    let parent = storage.get_block_by_hash(&block.header.prev_hash)
+        .ok_or_else(|| BlockError::UnknownParent(block.header.prev_hash.clone()))?;
+    if block.header.index != parent.header.index + 1 {
+        return Err(BlockError::HeightMismatch { expected: parent.header.index + 1, found: block.header.index });
+    }
+
+    // 2. Gene cấm & Chữ ký vĩnh cửu
+    if spec.is_forbidden_gene(block.header.index) {
+        return Err(BlockError::ForbiddenGene(block.header.index));
+    }
+    if !block.forbidden_gene_checked {
+        return Err(BlockError::GeneCheckMissing);
+    }
+    if block.eternal_signature != ETERNAL_SIGNATURE {
+        return Err(BlockError::InvalidEternalSignature);
+    }
+    // Coinbase được trả vào địa chỉ miner nên nó phải là địa chỉ hợp lệ
+    if !is_valid_address(&block.header.miner) {
+        return Err(BlockError::InvalidMiner(block.header.miner.clone()));
+    }
+
+    // Giới hạn kích thước theo block policy của mạng
+    let policy = &spec.block_policy;
+    let bytes: u64 = block.transactions.iter().map(|tx| tx.encoded_size()).sum();
+    if block.transactions.len() > policy.max_txs || bytes > policy.max_bytes {
+        return Err(BlockError::BlockTooLarge { txs: block.transactions.len(), bytes });
+    }
+
+    // 3. Thời gian: không lùi so với block cha, không vượt quá tương lai
+    if block.header.timestamp < parent.header.timestamp {
+        return Err(BlockError::TimestampBeforeParent { parent: parent.header.timestamp, found: block.header.timestamp });
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    if block.header.timestamp > now + MAX_BLOCK_FUTURE_DRIFT {
+        return Err(BlockError::TimestampInFuture { now, found: block.header.timestamp });
+    }
+
+    // 4. tx id phải đúng là hash payload, tx_root phải khớp, hash phải khớp với nội dung
+    let tx_hashes = block.tx_hashes();
+    if let Some((tx, computed)) = block.transactions.iter().zip(&tx_hashes).find(|(tx, h)| &tx.id != *h) {
+        return Err(BlockError::TxIdMismatch { claimed: tx.id.clone(), computed: computed.clone() });
+    }
+    let computed_root = merkle_root(&tx_hashes);
+    if block.header.tx_root != computed_root {
+        return Err(BlockError::TxRootMismatch { claimed: block.header.tx_root.clone(), computed: computed_root });
+    }
+    let computed = block.calculate_hash();
+    if block.hash != computed {
+        return Err(BlockError::HashMismatch { claimed: block.hash.clone(), computed });
+    }
+
+    // 5. Chain id, mã hóa chuẩn & chữ ký từng giao dịch
+    for tx in &block.transactions {
+        tx.check_chain(&spec.chain_id)
+            .and_then(|_| tx.check_integrity())
+            .map_err(|reason| BlockError::RejectedTransaction { id: tx.id.clone(), reason })?;
+        if !tx.verify() {
+            return Err(BlockError::InvalidTransaction(tx.id.clone()));
+        }
+    }
+
+    // 6. Proof of Intelligence: chạy lại SNN từ trạng thái của block cha
+    let snn = consensus_snn(storage, &spec.snn, &block.header.prev_hash)
+        .ok_or_else(|| BlockError::MissingSnnState(block.header.prev_hash.clone()))?;
+    let proof = snn.prove(block.header.index);
+    if proof.snn_root != block.header.snn_root {
+        return Err(BlockError::SnnRootMismatch { expected: proof.snn_root, found: block.header.snn_root.clone() });
+    }
+    if proof.score != block.header.spike_score {
+        return Err(BlockError::SpikeScoreMismatch { expected: proof.score, found: block.header.spike_score });
+    }
+
+    Ok(BlockEffects { snn_state: proof.state })
+}
+
+/// Thực thi giao dịch và coinbase của block trên trạng thái on-chain
+/// rồi đối chiếu state_root trong header. `overlay` phải đang ở trạng thái của block cha.
+pub fn execute_block<S: ChainStore>(block: &Block, spec: &ChainSpec, overlay: &mut StateOverlay<S>) -> Result<UndoLog, BlockError> {
+    let reward = spec.emission.reward_at(block.header.index);
+    let undo = overlay.apply_block(block, reward)
+        .map_err(|(id, reason)| BlockError::RejectedTransaction { id, reason })?;
+    let computed = overlay.state_root();
+    if computed != block.header.state_root {
+        return Err(BlockError::StateRootMismatch { claimed: block.header.state_root.clone(), computed });
+    }
+    Ok(undo)
+}
+
+/// Kiểm tra, thực thi rồi ghi một block nối tiếp đỉnh chuỗi chính (import archive, fast sync).
+/// Không xử lý nhánh phụ: block không nối vào đỉnh hiện tại bị từ chối.
+pub fn extend_head<S: ChainStore>(block: &Block, storage: &S, spec: &ChainSpec) -> Result<(), BlockError> {
+    let head = storage.get_last_hash();
+    if block.header.prev_hash != head {
+        return Err(BlockError::NotExtendingHead { head, parent: block.header.prev_hash.clone() });
+    }
+    let effects = validate_block(block, storage, spec)?;
+    let mut overlay = StateOverlay::new(storage);
+    let undo = execute_block(block, spec, &mut overlay)?;
+    storage.save_block(block, &effects.snn_state, &overlay.into_changes(), &undo)?;
+    Ok(())
+}
@@ -5,6 +5,22 @@ use bip39::{Mnemonic, Language};
 use sha2::{Sha256, Digest};
 use serde::{Serialize, Deserialize};
 
+/// Address = PAPPAP + Hex(SHA256(PublicKey)[0..16])
+pub fn address_from_public_key(public_key: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(public_key);
+    let address_hash = hex::encode(&hasher.finalize()[0..16]);
+    format!("PAPPAP{}", address_hash).to_uppercase()
+}
+
+/// Địa chỉ hợp lệ: "PAPPAP" + 32 ký tự hex viết hoa
+pub fn is_valid_address(address: &str) -> bool {
+    match address.strip_prefix("PAPPAP") {
+        Some(hash) => hash.len() == 32 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'A'..=b'F')),
+        None => false,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Wallet {
     pub address: String,
@@ -33,11 +49,7 @@ impl Wallet {
         let verifying_key = VerifyingKey::from(&signing_key);
         
         // 4. Tạo địa chỉ PAPPAP
-        // Address = PAPPAP + Hex(SHA256(PublicKey)[0..16])
-        let mut hasher = Sha256::new();
-        hasher.update(verifying_key.to_bytes());
-        let address_hash = hex::encode(&hasher.finalize()[0..16]);
-        let address = format!("PAPPAP{}", address_hash).to_uppercase();
+        let address = address_from_public_key(&verifying_key.to_bytes());
 
         println!("🔑 NEW WALLET GENERATED: {}", address);
 
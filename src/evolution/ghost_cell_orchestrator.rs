@@ -1,24 +1,24 @@
 // src/evolution/ghost_cell_orchestrator.rs
 use std::time::{SystemTime, UNIX_EPOCH};
-use crate::constants::GHOST_CELL_DEATH; //
 
 pub struct GhostCellOrchestrator {
     born_at: u64,
+    lifespan: u64, // Thời gian sống (giây), lấy từ chain spec
 }
 
 impl GhostCellOrchestrator {
     /// Khởi tạo tế bào ma với thời điểm sinh ra (Genesis Timestamp)
-    pub fn new(genesis_timestamp: u64) -> Self {
-        Self { born_at: genesis_timestamp }
+    pub fn new(genesis_timestamp: u64, lifespan: u64) -> Self {
+        Self { born_at: genesis_timestamp, lifespan }
     }
 
     /// Kiểm tra sinh hiệu. Trả về true nếu còn sống, false nếu đã chết già.
     pub fn check_vitality(&self) -> bool {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        let age = now - self.born_at;
+        let age = now.saturating_sub(self.born_at); // Genesis trong tương lai (devnet) -> tuổi 0
 
-        if age > GHOST_CELL_DEATH {
-            println!("⚰️  GHOST CELL DEATH: Age {}s > Limit {}s. Initiating Shutdown.", age, GHOST_CELL_DEATH);
+        if age > self.lifespan {
+            println!("⚰️  GHOST CELL DEATH: Age {}s > Limit {}s. Initiating Shutdown.", age, self.lifespan);
             return false;
         }
         
@@ -30,10 +30,10 @@ impl GhostCellOrchestrator {
 
     pub fn time_remaining(&self) -> u64 {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        if now > self.born_at + GHOST_CELL_DEATH {
+        if now > self.born_at + self.lifespan {
             0
         } else {
-            (self.born_at + GHOST_CELL_DEATH) - now
+            (self.born_at + self.lifespan) - now
         }
     }
 }
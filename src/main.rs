@@ -1,16 +1,24 @@
 // src/main.rs
 mod constants;
+mod config;
 mod ethics;
 mod evolution { pub mod ghost_cell_orchestrator; }
 mod core {
     pub mod block; pub mod chain; pub mod transaction;
     pub mod wallet; pub mod storage; pub mod governance;
+    pub mod validation; pub mod fork_choice; pub mod state;
+    pub mod merkle; pub mod chain_spec; pub mod migration;
+    pub mod sled_store; pub mod memory_store; pub mod archive;
+    pub mod snapshot;
 }
 mod ai {
     pub mod snn; pub mod snn_core; pub mod cache;
     pub mod tools; pub mod trainer;
 }
-mod network { pub mod p2p; pub mod webnode; }
+mod network {
+    pub mod p2p; pub mod webnode; pub mod sync;
+    pub mod message; pub mod peers; pub mod bootstrap;
+}
 mod persona {
     pub mod membrane { pub mod signal_sanitizer; }
     pub mod symbiosis { pub mod render_params; }
@@ -22,15 +30,22 @@ pub mod genetics {
     include!(concat!(env!("OUT_DIR"), "/pappap.genetics.rs"));
 }
 
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::sync::{Arc, atomic::AtomicUsize};
 use tokio::sync::{Mutex, mpsc};
 use actix_web::{App, HttpServer, web, middleware};
-use libp2p::identity;
 
 use crate::evolution::ghost_cell_orchestrator::GhostCellOrchestrator;
-use crate::core::{chain::PappapChain, storage::Storage, governance::NeuroDAO, transaction::Mempool};
+use crate::core::{chain::PappapChain, governance::NeuroDAO, transaction::Mempool, wallet::Wallet};
+use crate::core::{storage::ChainStore, sled_store::SledStore, memory_store::MemoryStore};
+use crate::config::{NodeConfig, StorageBackend};
+use crate::core::chain_spec::{ensure_genesis, ChainSpec};
+use crate::core::archive::{export_chain, import_chain};
+use crate::core::snapshot::fast_sync;
 use crate::ai::{cache::SmartCache, snn_core::SNNCore, trainer::AutoTrainer};
-use crate::network::{p2p::P2PNode, webnode::WebNodeManager};
+use crate::network::{p2p::{load_node_key, P2PNode}, webnode::WebNodeManager};
+use crate::network::sync::{run_sync, serve_sync};
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -38,25 +53,60 @@ async fn main() -> std::io::Result<()> {
     env_logger::init();
     println!("🌌 PAPPAP AI NODE v0.8.1 (AUDITED)");
 
+    // 0. CHAIN SPEC (CHAIN_SPEC=<file.json>, mặc định: mainnet)
+    let spec = Arc::new(ChainSpec::from_env().expect("💀 CHAIN SPEC INVALID"));
+    println!("🧬 CHAIN: {}", spec.chain_id);
+
     // 1. GENETICS CHECK
-    let ghost_cell = GhostCellOrchestrator::new(1700000000);
+    let ghost_cell = GhostCellOrchestrator::new(spec.genesis_timestamp, spec.ghost_cell_lifespan);
     if !ghost_cell.check_vitality() { panic!("💀 GHOST CELL EXPIRED"); }
 
+    // Cấu hình node (NODE_CONFIG=<file.json>, MINER_ADDRESS, STORAGE_BACKEND, DATA_DIR, LISTEN_ADDRS, BOOTSTRAP_PEERS...)
+    let node_config = NodeConfig::from_env().expect("💀 NODE CONFIG INVALID");
+
     // 2. DATA
-    let storage = Arc::new(Storage::new("pappap_v1.db"));
-    let mempool = Arc::new(Mempool::new());
+    match node_config.storage {
+        StorageBackend::Sled => {
+            let storage = SledStore::new(node_config.data_dir()).map_err(std::io::Error::other)?;
+            run_node(spec, node_config, Arc::new(storage)).await
+        }
+        StorageBackend::Memory => run_node(spec, node_config, Arc::new(MemoryStore::new())).await,
+    }
+}
+
+/// Khởi động node trên một backend lưu trữ bất kỳ
+async fn run_node<S: ChainStore>(spec: Arc<ChainSpec>, node_config: NodeConfig, storage: Arc<S>) -> std::io::Result<()> {
+    ensure_genesis(&*storage, &spec).expect("💀 GENESIS MISMATCH");
+
+    // Lệnh bảo trì (vd. `pappap-ai-chain reindex`, `export <file>`): chạy xong thì thoát, không khởi động node
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return run_command(&args, &*storage, &spec);
+    }
+
+    let miner_address = node_config.miner_address()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    println!("⛏️  MINER ADDRESS: {}", miner_address);
+
+    let mempool = Arc::new(Mempool::new(storage.clone(), spec.chain_id.clone()));
     let cache = SmartCache::new();
-    let dao = Arc::new(NeuroDAO::new());
+    let dao = Arc::new(NeuroDAO::new(storage.clone()));
     let wn_mgr = Arc::new(WebNodeManager::new());
 
     // 3. NETWORK (P2P)
-    let local_key = identity::Keypair::generate_ed25519();
+    let network = node_config.network().expect("💀 NETWORK CONFIG INVALID");
+    let local_key = load_node_key(node_config.node_key_file().as_deref())?;
     let peer_count = Arc::new(AtomicUsize::new(0));
+    let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
+    let (sync_inbound_tx, sync_inbound_rx) = mpsc::unbounded_channel();
 
     // [FIX] Nhận về p2p_sender (command channel) thay vì receiver
-    let (mut p2p_node, p2p_sender, local_peer_id) = P2PNode::new(local_key, peer_count.clone())
+    let (mut p2p_node, p2p_sender, local_peer_id) = P2PNode::new(local_key, &spec.chain_id, &network, peer_count.clone(), inbound_tx, sync_inbound_tx)
         .await
         .expect("P2P Init Failed");
+    let sync_client = p2p_node.sync_client();
+    let verdict_tx = p2p_node.verdict_sender();
+    let peer_book = p2p_node.peer_book();
     
     let p2p_arc = Arc::new(Mutex::new(p2p_node));
     println!("🆔 NODE ID: {}", local_peer_id);
@@ -65,9 +115,12 @@ async fn main() -> std::io::Result<()> {
     let snn_core = Arc::new(SNNCore::new(storage.clone(), cache.clone()));
     
     let chain = Arc::new(PappapChain::new(
+        spec.clone(),
         storage.clone(),
         mempool.clone(),
         snn_core.clone(),
+        miner_address,
+        node_config.snapshot_interval(),
         p2p_sender, // Truyền Sender vào Chain
     ).await);
 
@@ -78,11 +131,35 @@ async fn main() -> std::io::Result<()> {
     tokio::spawn(async move {
         p2p_runner.lock().await.run().await;
     });
+    tokio::spawn(serve_sync(storage.clone(), spec.clone(), sync_client.clone(), sync_inbound_rx));
+
+    // Fast sync: DB chỉ có genesis thì tải snapshot qua P2P thay vì chạy lại từ đầu (trước khi bắt đầu đào)
+    if node_config.fast_sync() && storage.get_height() == 0 {
+        println!("🚀 FAST SYNC: waiting for peers");
+        match fast_sync(&*storage, &spec, &sync_client).await {
+            Ok(height) => println!("✅ FAST SYNC complete, head is #{}", height),
+            Err(e) => println!("⚠️ FAST SYNC failed, continuing from local chain: {}", e),
+        }
+    }
 
     // Task B: Mining
     let chain_miner = chain.clone();
     tokio::spawn(async move { chain_miner.run().await; });
 
+    // Task B2: Import Block nhận từ mạng
+    let chain_importer = chain.clone();
+    tokio::spawn(async move { chain_importer.run_importer(inbound_rx, verdict_tx).await; });
+
+    // Task B3: Chain sync (tải phần chuỗi còn thiếu; server sync đã chạy cùng P2P)
+    let chain_sync = chain.clone();
+    tokio::spawn(async move { run_sync(chain_sync, sync_client).await; });
+
+    // Task B4: Pruning (chỉ khi cấu hình prune_keep_blocks)
+    if let Some(keep) = node_config.prune_keep_blocks {
+        let chain_pruner = chain.clone();
+        tokio::spawn(async move { chain_pruner.run_pruner(keep).await; });
+    }
+
     // Task C: Training
     let ai_trainer = snn_core.clone();
     tokio::spawn(async move { AutoTrainer::start(ai_trainer).await; });
@@ -92,6 +169,7 @@ async fn main() -> std::io::Result<()> {
     tokio::spawn(async move { wn_pruner.prune_offline().await; });
 
     // 6. API
+    let api_bind = node_config.api_bind().to_string();
     HttpServer::new(move || {
         App::new()
             .wrap(middleware::Logger::default())
@@ -102,11 +180,61 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(dao.clone()))
             .app_data(web::Data::new(wn_mgr.clone()))
             .app_data(web::Data::new(snn_core.clone()))
+            .app_data(web::Data::new(peer_book.clone()))
             // .app_data(web::Data::new(peer_count.clone())) // Nếu cần hiển thị peers
             // Load Routes từ module API
-            .configure(crate::api::routes::config)
+            .configure(crate::api::routes::config::<S>)
     })
-    .bind("0.0.0.0:8080")?
+    .bind(api_bind)?
     .run()
     .await
 }
+
+/// Các lệnh bảo trì chạy trên database rồi thoát
+fn run_command<S: ChainStore>(args: &[String], storage: &S, spec: &ChainSpec) -> std::io::Result<()> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+    // Tham số tùy chọn `to_height` của export/import
+    let to_height = match args.get(2) {
+        Some(raw) => Some(raw.parse::<u64>().map_err(|_| invalid(format!("Invalid height '{}'", raw)))?),
+        None => None,
+    };
+
+    match args[0].as_str() {
+        "reindex" => {
+            println!("🗂️  REINDEXING transactions...");
+            let count = storage.reindex().map_err(std::io::Error::other)?;
+            println!("✅ Reindexed {} transactions up to block #{}", count, storage.get_height());
+            Ok(())
+        }
+        "export" => {
+            let path = args.get(1).ok_or_else(|| invalid("Usage: export <file> [to_height]".to_string()))?;
+            let mut writer = BufWriter::new(File::create(path)?);
+            let header = export_chain(storage, spec, &mut writer, to_height).map_err(std::io::Error::other)?;
+            println!("✅ Exported blocks #0..#{} of {} to {}", header.height, header.chain_id, path);
+            Ok(())
+        }
+        "import" => {
+            let path = args.get(1).ok_or_else(|| invalid("Usage: import <file> [to_height]".to_string()))?;
+            let mut reader = BufReader::new(File::open(path)?);
+            println!("📥 IMPORTING archive {}...", path);
+            let count = import_chain(storage, spec, &mut reader, to_height).map_err(std::io::Error::other)?;
+            println!("✅ Imported {} blocks, head is now #{}", count, storage.get_height());
+            Ok(())
+        }
+        "new-wallet" => {
+            let path = args.get(1).ok_or_else(|| invalid("Usage: new-wallet <file>".to_string()))?;
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600); // File chứa mnemonic: chỉ chủ node đọc được
+            let wallet = Wallet::new();
+            serde_json::to_writer_pretty(options.open(path)?, &wallet).map_err(std::io::Error::other)?;
+            println!("✅ Wallet {} saved to {}. Back it up, its mnemonic controls the rewards", wallet.address, path);
+            Ok(())
+        }
+        other => Err(invalid(format!(
+            "Unknown command '{}'. Available: reindex, export <file> [to_height], import <file> [to_height], new-wallet <file>",
+            other
+        ))),
+    }
+}
@@ -0,0 +1,104 @@
+// src/network/bootstrap.rs
+// Giữ kết nối tới các bootstrap peer trong cấu hình: quay số khi khởi động,
+// quay lại khi mất kết nối hoặc quay số lỗi, thời gian chờ nhân đôi sau mỗi lần lỗi.
+use crate::constants::{BOOTSTRAP_RETRY_MAX_SECS, BOOTSTRAP_RETRY_MIN_SECS};
+use libp2p::multiaddr::Protocol;
+use libp2p::swarm::{dial_opts::{DialOpts, PeerCondition}, ConnectionId};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+enum DialState {
+    Waiting { retry_at: Instant },
+    Dialing,
+    Connected,
+}
+
+struct BootstrapPeer {
+    addr: Multiaddr,
+    peer_id: Option<PeerId>, // Lấy từ /p2p/<id> trong địa chỉ, hoặc khi kết nối thành công
+    state: DialState,
+    backoff: Duration,
+}
+
+pub struct BootstrapPeers {
+    peers: Vec<BootstrapPeer>,
+    dialing: HashMap<ConnectionId, usize>, // Lượt quay số đang chờ -> chỉ số trong `peers`
+}
+
+fn min_backoff() -> Duration {
+    Duration::from_secs(BOOTSTRAP_RETRY_MIN_SECS)
+}
+
+impl BootstrapPeers {
+    pub fn new(addrs: Vec<Multiaddr>) -> Self {
+        let now = Instant::now();
+        let peers = addrs.into_iter()
+            .map(|addr| BootstrapPeer {
+                peer_id: addr.iter().find_map(|p| match p {
+                    Protocol::P2p(peer_id) => Some(peer_id),
+                    _ => None,
+                }),
+                addr,
+                state: DialState::Waiting { retry_at: now },
+                backoff: min_backoff(),
+            })
+            .collect();
+        Self { peers, dialing: HashMap::new() }
+    }
+
+    /// Lượt quay số tới hạn. `is_connected` cho biết peer đã kết nối theo đường khác (mDNS, peer tự quay tới).
+    pub fn due_dials(&mut self, is_connected: impl Fn(&PeerId) -> bool) -> Vec<DialOpts> {
+        let now = Instant::now();
+        let mut dials = Vec::new();
+        for (index, peer) in self.peers.iter_mut().enumerate() {
+            if !matches!(peer.state, DialState::Waiting { retry_at } if retry_at <= now) {
+                continue;
+            }
+            let opts = match peer.peer_id {
+                Some(peer_id) if is_connected(&peer_id) => {
+                    peer.state = DialState::Connected;
+                    continue;
+                }
+                Some(peer_id) => DialOpts::peer_id(peer_id)
+                    .addresses(vec![peer.addr.clone()])
+                    .condition(PeerCondition::DisconnectedAndNotDialing)
+                    .build(),
+                None => DialOpts::unknown_peer_id().address(peer.addr.clone()).build(),
+            };
+            self.dialing.insert(opts.connection_id(), index);
+            peer.state = DialState::Dialing;
+            dials.push(opts);
+        }
+        dials
+    }
+
+    pub fn on_connected(&mut self, connection_id: ConnectionId, peer_id: PeerId) {
+        if let Some(index) = self.dialing.remove(&connection_id) {
+            let peer = &mut self.peers[index];
+            peer.peer_id = Some(peer_id);
+            peer.state = DialState::Connected;
+            peer.backoff = min_backoff();
+        }
+    }
+
+    /// Quay số lỗi: hẹn lần sau, trả về địa chỉ & thời gian chờ (để log). None nếu không phải bootstrap peer.
+    pub fn on_dial_failed(&mut self, connection_id: ConnectionId) -> Option<(Multiaddr, Duration)> {
+        let index = self.dialing.remove(&connection_id)?;
+        let peer = &mut self.peers[index];
+        let wait = peer.backoff;
+        peer.state = DialState::Waiting { retry_at: Instant::now() + wait };
+        peer.backoff = (wait * 2).min(Duration::from_secs(BOOTSTRAP_RETRY_MAX_SECS));
+        Some((peer.addr.clone(), wait))
+    }
+
+    /// Peer ngắt hết kết nối: quay lại sau thời gian chờ hiện tại
+    pub fn on_disconnected(&mut self, peer_id: &PeerId) {
+        let now = Instant::now();
+        for peer in &mut self.peers {
+            if matches!(peer.state, DialState::Connected) && peer.peer_id.as_ref() == Some(peer_id) {
+                peer.state = DialState::Waiting { retry_at: now + peer.backoff };
+            }
+        }
+    }
+}
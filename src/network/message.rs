@@ -0,0 +1,155 @@
+// src/network/message.rs
+// Message gossip có kiểu và phiên bản. Mỗi loại message đi trên topic riêng của chain:
+// pappap/<chain_id>/blocks, /txs, /governance, /ai.
+//
+// Định dạng trên dây: Envelope mã hóa bincode { version, kind, payload }, payload là bincode của block/giao dịch.
+// Đề xuất, bỏ phiếu, fact và thưởng worker đều là giao dịch có ký nên đi dưới kind Transaction;
+// topic (governance, ai) được suy ra từ TxKind và phải khớp với topic message thực sự đến.
+use crate::core::block::Block;
+use crate::core::transaction::{Transaction, TxKind};
+use libp2p::gossipsub::{IdentTopic, MessageAcceptance, MessageId, TopicHash};
+use libp2p::PeerId;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+// Tăng khi đổi định dạng message; node bỏ qua message khác phiên bản
+pub const NETWORK_MESSAGE_VERSION: u16 = 1;
+
+#[derive(Clone, Debug)]
+pub enum NetworkMessage {
+    Block(Block),
+    Transaction(Transaction),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum MessageKind {
+    Block,
+    Transaction,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u16,
+    kind: MessageKind,
+    payload: Vec<u8>,
+}
+
+/// Các topic gossip của một chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GossipTopic {
+    Blocks,
+    Transactions, // Chuyển khoản
+    Governance, // Đề xuất & bỏ phiếu DAO
+    Ai, // Fact cho AI và thưởng WebNode worker
+}
+
+impl GossipTopic {
+    pub const ALL: [GossipTopic; 4] = [
+        GossipTopic::Blocks,
+        GossipTopic::Transactions,
+        GossipTopic::Governance,
+        GossipTopic::Ai,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GossipTopic::Blocks => "blocks",
+            GossipTopic::Transactions => "txs",
+            GossipTopic::Governance => "governance",
+            GossipTopic::Ai => "ai",
+        }
+    }
+
+    /// Topic gắn chain id để node của các chain khác nhau không nhận nhầm message của nhau
+    pub fn ident(&self, chain_id: &str) -> IdentTopic {
+        IdentTopic::new(format!("pappap/{}/{}", chain_id, self.name()))
+    }
+}
+
+#[derive(Debug)]
+pub enum MessageError {
+    Encode(String),
+    Decode(String),
+    UnsupportedVersion(u16),
+    WrongTopic { expected: GossipTopic, received: TopicHash },
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageError::Encode(e) => write!(f, "cannot encode message: {}", e),
+            MessageError::Decode(e) => write!(f, "malformed message: {}", e),
+            MessageError::UnsupportedVersion(v) => write!(
+                f, "message version {} is not supported (expected {})", v, NETWORK_MESSAGE_VERSION
+            ),
+            MessageError::WrongTopic { expected, received } => write!(
+                f, "message belongs on the {} topic but arrived on {}", expected.name(), received
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+impl NetworkMessage {
+    pub fn topic(&self) -> GossipTopic {
+        match self {
+            NetworkMessage::Block(_) => GossipTopic::Blocks,
+            NetworkMessage::Transaction(tx) => match tx.kind {
+                TxKind::Transfer { .. } => GossipTopic::Transactions,
+                TxKind::Proposal { .. } | TxKind::Vote { .. } => GossipTopic::Governance,
+                TxKind::KnowledgeFact { .. } | TxKind::WorkerReward { .. } => GossipTopic::Ai,
+            },
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, MessageError> {
+        let (kind, payload) = match self {
+            NetworkMessage::Block(block) => (MessageKind::Block, bincode::serialize(block)),
+            NetworkMessage::Transaction(tx) => (MessageKind::Transaction, bincode::serialize(tx)),
+        };
+        let payload = payload.map_err(|e| MessageError::Encode(e.to_string()))?;
+        let envelope = Envelope { version: NETWORK_MESSAGE_VERSION, kind, payload };
+        bincode::serialize(&envelope).map_err(|e| MessageError::Encode(e.to_string()))
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, MessageError> {
+        let envelope: Envelope = bincode::deserialize(data).map_err(|e| MessageError::Decode(e.to_string()))?;
+        if envelope.version != NETWORK_MESSAGE_VERSION {
+            return Err(MessageError::UnsupportedVersion(envelope.version));
+        }
+        let decode_err = |e: bincode::Error| MessageError::Decode(e.to_string());
+        match envelope.kind {
+            MessageKind::Block => bincode::deserialize(&envelope.payload).map(NetworkMessage::Block).map_err(decode_err),
+            MessageKind::Transaction => {
+                bincode::deserialize(&envelope.payload).map(NetworkMessage::Transaction).map_err(decode_err)
+            }
+        }
+    }
+
+    /// Giải mã message gossip nhận trên `topic`; message đi sai topic của loại nó bị coi là không hợp lệ
+    pub fn decode_on(data: &[u8], chain_id: &str, topic: &TopicHash) -> Result<Self, MessageError> {
+        let msg = Self::decode(data)?;
+        let expected = msg.topic();
+        if expected.ident(chain_id).hash() != *topic {
+            return Err(MessageError::WrongTopic { expected, received: topic.clone() });
+        }
+        Ok(msg)
+    }
+}
+
+/// Message gossip nhận từ peer. Gossipsub giữ lại, chỉ chuyển tiếp sau khi Chain kiểm tra xong.
+#[derive(Debug)]
+pub struct InboundMessage {
+    pub message: NetworkMessage,
+    pub id: MessageId,
+    pub source: PeerId, // Peer chuyển message tới (không nhất thiết là tác giả)
+}
+
+/// Kết quả kiểm tra một InboundMessage, trả lại P2PNode để báo cho gossipsub
+#[derive(Debug)]
+pub struct Verdict {
+    pub id: MessageId,
+    pub source: PeerId,
+    pub acceptance: MessageAcceptance,
+}
@@ -1,2 +1,6 @@
 pub mod p2p;
 pub mod webnode;
+pub mod sync;
+pub mod message;
+pub mod peers;
+pub mod bootstrap;
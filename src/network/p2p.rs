@@ -1,52 +1,116 @@
 // src/network/p2p.rs
 use libp2p::{
-    gossipsub, identity, noise, tcp, yamux,
-    swarm::{NetworkBehaviour, SwarmEvent},
-    PeerId, Swarm,
+    allow_block_list, gossipsub, identify, identity, kad, mdns, noise, request_response, tcp, yamux,
+    swarm::{behaviour::toggle::Toggle, dial_opts::{DialOpts, PeerCondition}, ConnectionId, NetworkBehaviour, SwarmEvent},
+    Multiaddr, PeerId, StreamProtocol, Swarm,
 };
 use libp2p::futures::StreamExt;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+use std::collections::HashMap;
 use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use crate::config::NetworkConfig;
+use crate::constants::{
+    GOSSIP_INVALID_MESSAGE_WEIGHT, KAD_DIAL_MAX_PEERS, KAD_WALK_SECS, MAX_GOSSIP_MESSAGE_BYTES, PEER_REFRESH_SECS,
+};
+use crate::network::bootstrap::BootstrapPeers;
+use crate::network::message::{GossipTopic, InboundMessage, NetworkMessage, Verdict};
+use crate::network::peers::PeerBook;
+use crate::network::sync::{self, InboundSync, SyncBehaviour, SyncClient, SyncCommand, SyncError, SyncEvent, SyncResponse};
 
 #[derive(NetworkBehaviour)]
 struct PappapBehaviour {
     gossipsub: gossipsub::Behaviour,
-    identify: libp2p::identify::Behaviour,
+    identify: identify::Behaviour,
+    // DHT Kademlia: tìm peer ngoài danh sách bootstrap
+    kad: kad::Behaviour<kad::store::MemoryStore>,
+    sync: SyncBehaviour,
+    // Peer bị ban: từ chối mọi kết nối tới khi hết hạn
+    blocked: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+    // Tìm peer trong LAN (tắt bằng NodeConfig.mdns = false)
+    mdns: Toggle<mdns::tokio::Behaviour>,
 }
 
 pub struct P2PNode {
     swarm: Swarm<PappapBehaviour>,
-    topic: gossipsub::IdentTopic,
+    chain_id: String, // Topic gossip gắn với chain id
     pub peer_count: Arc<AtomicUsize>,
     // [FIX] Đưa receiver vào trong struct để quản lý luồng
-    command_rx: mpsc::UnboundedReceiver<Vec<u8>>, 
+    command_rx: mpsc::UnboundedReceiver<NetworkMessage>,
+    // Chuyển message Gossip đã giải mã sang Chain để kiểm tra & import
+    inbound_tx: mpsc::UnboundedSender<InboundMessage>,
+    // Chain trả kết quả kiểm tra: gossipsub chỉ chuyển tiếp message hợp lệ
+    verdict_tx: mpsc::UnboundedSender<Verdict>,
+    verdict_rx: mpsc::UnboundedReceiver<Verdict>,
+    peers: Arc<PeerBook>,
+    // Bootstrap peer trong cấu hình, tự kết nối lại khi mất
+    bootstrap: BootstrapPeers,
+    // Lệnh sync từ SyncClient (gửi request, trả lời peer, liệt kê peer)
+    sync_tx: mpsc::UnboundedSender<SyncCommand>,
+    sync_rx: mpsc::UnboundedReceiver<SyncCommand>,
+    // Request sync đã gửi, chờ response của peer
+    sync_pending: HashMap<request_response::OutboundRequestId, oneshot::Sender<Result<SyncResponse, SyncError>>>,
+    // Request sync của peer, chuyển cho serve_sync trả lời
+    sync_inbound_tx: mpsc::UnboundedSender<InboundSync>,
 }
 
 impl P2PNode {
     pub async fn new(
         local_key: identity::Keypair, 
-        peer_count: Arc<AtomicUsize>
-    ) -> Result<(Self, mpsc::UnboundedSender<Vec<u8>>, PeerId), Box<dyn Error>> {
+        chain_id: &str,
+        network: &NetworkConfig,
+        peer_count: Arc<AtomicUsize>,
+        inbound_tx: mpsc::UnboundedSender<InboundMessage>,
+        sync_inbound_tx: mpsc::UnboundedSender<InboundSync>,
+    ) -> Result<(Self, mpsc::UnboundedSender<NetworkMessage>, PeerId), Box<dyn Error>> {
         let local_peer_id = PeerId::from(local_key.public());
         
         // Setup Gossip & Identify (Giữ nguyên code cũ)
-        let topic = gossipsub::IdentTopic::new("pappap-mainnet");
         let gossip_config = gossipsub::ConfigBuilder::default()
             .heartbeat_interval(Duration::from_secs(1))
+            .max_transmit_size(MAX_GOSSIP_MESSAGE_BYTES)
             .validation_mode(gossipsub::ValidationMode::Strict)
+            .validate_messages() // Chờ Chain kiểm tra block/tx rồi mới chuyển tiếp
             .build()
             .map_err(|e| format!("Config error: {}", e))?;
 
+        let mut gossipsub = gossipsub::Behaviour::new(
+            gossipsub::MessageAuthenticity::Signed(local_key.clone()),
+            gossip_config,
+        )?;
+        gossipsub.with_peer_score(peer_score_params(chain_id), gossipsub::PeerScoreThresholds::default())?;
+
+        let mdns = if network.mdns {
+            Some(mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)?)
+        } else {
+            None
+        };
+
+        // Mỗi chain một DHT riêng, để node của chain khác không lọt vào routing table
+        let kad_protocol = StreamProtocol::try_from_owned(format!("/pappap/{}/kad/1.0.0", chain_id))?;
+        let kad_config = kad::Config::new(kad_protocol);
+        let mut kad = kad::Behaviour::with_config(local_peer_id, kad::store::MemoryStore::new(local_peer_id), kad_config);
+        // Luôn trả lời truy vấn DHT (chế độ tự động cần địa chỉ công khai đã xác nhận, node LAN không có)
+        kad.set_mode(Some(kad::Mode::Server));
+        for addr in &network.bootstrap_peers {
+            if let Some((peer, addr)) = split_peer_id(addr) {
+                kad.add_address(&peer, addr);
+            }
+        }
+
         let behaviour = PappapBehaviour {
-            gossipsub: gossipsub::Behaviour::new(
-                gossipsub::MessageAuthenticity::Signed(local_key.clone()),
-                gossip_config,
-            )?,
-            identify: libp2p::identify::Behaviour::new(
-                libp2p::identify::Config::new("pappap/0.8.0".into(), local_key.public())
+            gossipsub,
+            identify: identify::Behaviour::new(
+                identify::Config::new("pappap/0.8.0".into(), local_key.public())
             ),
+            kad,
+            sync: sync::new_behaviour(),
+            blocked: allow_block_list::Behaviour::default(),
+            mdns: mdns.into(),
         };
 
         let mut swarm = libp2p::SwarmBuilder::with_existing_identity(local_key)
@@ -56,50 +120,333 @@ impl P2PNode {
             .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
             .build();
 
-        swarm.listen_on("/ip4/0.0.0.0/tcp/9000".parse()?)?;
+        for addr in &network.listen_addrs {
+            swarm.listen_on(addr.clone())?;
+        }
         
-        // Subscribe topic
-        swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
+        // Subscribe topic: blocks, txs, governance, ai
+        for topic in GossipTopic::ALL {
+            swarm.behaviour_mut().gossipsub.subscribe(&topic.ident(chain_id))?;
+        }
 
         // [FIX] Tạo channel tại đây và trả về Sender cho Main
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (sync_tx, sync_rx) = mpsc::unbounded_channel();
+        let (verdict_tx, verdict_rx) = mpsc::unbounded_channel();
+
+        let node = Self {
+            swarm,
+            chain_id: chain_id.to_string(),
+            peer_count,
+            command_rx: cmd_rx,
+            inbound_tx,
+            verdict_tx,
+            verdict_rx,
+            peers: Arc::new(PeerBook::default()),
+            bootstrap: BootstrapPeers::new(network.bootstrap_peers.clone()),
+            sync_tx,
+            sync_rx,
+            sync_pending: HashMap::new(),
+            sync_inbound_tx,
+        };
+        Ok((node, cmd_tx, local_peer_id))
+    }
+
+    /// Client gửi request sync (trạng thái, header, block) tới peer qua node này
+    pub fn sync_client(&self) -> SyncClient {
+        SyncClient::new(self.sync_tx.clone())
+    }
+
+    /// Kênh để Chain trả kết quả kiểm tra message gossip
+    pub fn verdict_sender(&self) -> mpsc::UnboundedSender<Verdict> {
+        self.verdict_tx.clone()
+    }
+
+    /// Điểm & trạng thái ban của các peer (cho API)
+    pub fn peer_book(&self) -> Arc<PeerBook> {
+        self.peers.clone()
+    }
+
+    /// Báo kết quả kiểm tra cho gossipsub; peer gửi quá nhiều message sai bị ban một thời gian
+    fn report(&mut self, verdict: Verdict) {
+        let rejected = matches!(verdict.acceptance, gossipsub::MessageAcceptance::Reject);
+        let gossipsub = &mut self.swarm.behaviour_mut().gossipsub;
+        let _ = gossipsub.report_message_validation_result(&verdict.id, &verdict.source, verdict.acceptance);
+        if !rejected || !self.peers.strike(verdict.source) {
+            return;
+        }
+        println!("⛔ Peer {} banned for repeatedly sending invalid blocks/txs", verdict.source);
+        gossipsub.blacklist_peer(&verdict.source);
+        self.swarm.behaviour_mut().blocked.block_peer(verdict.source);
+    }
+
+    /// Cập nhật điểm gossipsub vào PeerBook và gỡ ban hết hạn
+    fn refresh_peers(&mut self) {
+        let behaviour = self.swarm.behaviour_mut();
+        for peer in self.peers.expire_bans() {
+            println!("🕊️ Ban lifted for peer {}", peer);
+            behaviour.gossipsub.remove_blacklisted_peer(&peer);
+            behaviour.blocked.unblock_peer(peer);
+        }
+        let peers: Vec<PeerId> = behaviour.gossipsub.all_peers().map(|(peer, _)| *peer).collect();
+        for peer in peers {
+            if let Some(score) = behaviour.gossipsub.peer_score(&peer) {
+                self.peers.set_score(&peer, score);
+            }
+        }
+    }
 
-        Ok((Self { swarm, topic, peer_count, command_rx: cmd_rx }, cmd_tx, local_peer_id))
+    /// Quay số các bootstrap peer tới hạn (lần đầu hoặc sau thời gian chờ)
+    fn dial_bootstrap(&mut self) {
+        let swarm = &self.swarm;
+        for opts in self.bootstrap.due_dials(|peer| swarm.is_connected(peer)) {
+            let connection_id = opts.connection_id();
+            if let Err(e) = self.swarm.dial(opts) {
+                self.bootstrap_failed(connection_id, &e.to_string());
+            }
+        }
+    }
+
+    fn bootstrap_failed(&mut self, connection_id: ConnectionId, error: &str) {
+        if let Some((addr, wait)) = self.bootstrap.on_dial_failed(connection_id) {
+            println!("⚠️ Bootstrap peer {} unreachable ({}), retrying in {}s", addr, error, wait.as_secs());
+        }
+    }
+
+    /// Quay số peer mDNS tìm thấy (bỏ qua peer đã kết nối hoặc đang quay số)
+    fn dial_discovered(&mut self, discovered: Vec<(PeerId, Multiaddr)>) {
+        let mut by_peer: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+        for (peer, addr) in discovered {
+            by_peer.entry(peer).or_default().push(addr);
+        }
+        for (peer, addrs) in by_peer {
+            for addr in &addrs {
+                self.swarm.behaviour_mut().kad.add_address(&peer, addr.clone());
+            }
+            let opts = DialOpts::peer_id(peer)
+                .addresses(addrs)
+                .condition(PeerCondition::DisconnectedAndNotDialing)
+                .build();
+            if self.swarm.dial(opts).is_ok() {
+                println!("🔎 Discovered peer {} on the local network", peer);
+            }
+        }
+    }
+
+    /// Quay số peer mới tìm được qua DHT khi node còn ít kết nối
+    fn dial_routed(&mut self, peer: PeerId, addresses: Vec<Multiaddr>) {
+        if self.swarm.connected_peers().count() >= KAD_DIAL_MAX_PEERS {
+            return;
+        }
+        let opts = DialOpts::peer_id(peer)
+            .addresses(addresses)
+            .condition(PeerCondition::DisconnectedAndNotDialing)
+            .build();
+        if self.swarm.dial(opts).is_ok() {
+            println!("🧭 Found peer {} through the DHT", peer);
+        }
+    }
+
+    /// Response trả về bên hỏi; request của peer chuyển cho serve_sync
+    fn on_sync_event(&mut self, event: SyncEvent) {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    if self.sync_inbound_tx.send(InboundSync { peer, request, channel }).is_err() {
+                        println!("⚠️ Sync server is not running, request from {} dropped", peer);
+                    }
+                }
+                request_response::Message::Response { request_id, response } => {
+                    if let Some(reply) = self.sync_pending.remove(&request_id) {
+                        let _ = reply.send(Ok(response));
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure { request_id, error, .. } => {
+                if let Some(reply) = self.sync_pending.remove(&request_id) {
+                    let _ = reply.send(Err(SyncError::Request(error)));
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                println!("⚠️ Sync request from {} failed: {}", peer, error);
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    /// Gửi message lên topic tương ứng với loại của nó
+    fn publish(&mut self, msg: &NetworkMessage) {
+        let topic = msg.topic();
+        let data = match msg.encode() {
+            Ok(data) => data,
+            Err(e) => {
+                println!("❌ Broadcast Failed: {}", e);
+                return;
+            }
+        };
+        match self.swarm.behaviour_mut().gossipsub.publish(topic.ident(&self.chain_id), data) {
+            Ok(_) => println!("📡 Broadcasted to {} topic", topic.name()),
+            Err(e) => println!("❌ Broadcast Failed: {:?}", e),
+        }
     }
 
     /// Vòng lặp chính xử lý cả Network Event và Command từ Chain
     pub async fn run(&mut self) {
         println!("🌐 P2P EVENT LOOP STARTED");
+        let mut refresh = tokio::time::interval(Duration::from_secs(PEER_REFRESH_SECS));
+        let mut kad_walk = tokio::time::interval(Duration::from_secs(KAD_WALK_SECS));
+        self.dial_bootstrap();
         loop {
             tokio::select! {
                 // 1. Xử lý sự kiện mạng (Swarm)
                 event = self.swarm.select_next_some() => {
                     match event {
                         SwarmEvent::NewListenAddr { address, .. } => println!("👂 Listening on {:?}", address),
-                        SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                        SwarmEvent::ConnectionEstablished { peer_id, connection_id, .. } => {
+                            self.bootstrap.on_connected(connection_id, peer_id);
                             self.peer_count.fetch_add(1, Ordering::Relaxed);
+                            self.peers.set_connected(peer_id, true);
                             println!("🤝 Connected: {:?}", peer_id);
                         },
-                        SwarmEvent::ConnectionClosed { peer_id, .. } => {
+                        SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
                             self.peer_count.fetch_sub(1, Ordering::Relaxed);
+                            if num_established == 0 {
+                                self.peers.set_connected(peer_id, false);
+                                self.bootstrap.on_disconnected(&peer_id);
+                            }
                             println!("🔌 Disconnected: {:?}", peer_id);
                         },
-                        SwarmEvent::Behaviour(PappapBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
-                            println!("📩 Gossip Message from {:?}", message.source);
-                            // TODO: Forward message to Mempool/Chain validation
+                        SwarmEvent::OutgoingConnectionError { connection_id, error, .. } => {
+                            self.bootstrap_failed(connection_id, &error.to_string());
                         },
+                        SwarmEvent::Behaviour(PappapBehaviourEvent::Mdns(mdns::Event::Discovered(discovered))) => {
+                            self.dial_discovered(discovered);
+                        },
+                        // Địa chỉ peer tự khai báo: đưa vào DHT nếu peer chạy cùng giao thức Kademlia
+                        SwarmEvent::Behaviour(PappapBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. })) => {
+                            let kad_protocols = self.swarm.behaviour().kad.protocol_names().to_vec();
+                            if info.protocols.iter().any(|p| kad_protocols.contains(p)) {
+                                for addr in info.listen_addrs {
+                                    self.swarm.behaviour_mut().kad.add_address(&peer_id, addr);
+                                }
+                            }
+                        },
+                        SwarmEvent::Behaviour(PappapBehaviourEvent::Kad(kad::Event::RoutingUpdated {
+                            peer, is_new_peer: true, addresses, ..
+                        })) => {
+                            // Peer đầu tiên trong routing table: walk ngay thay vì chờ chu kỳ kế tiếp
+                            let kad = &mut self.swarm.behaviour_mut().kad;
+                            if kad.kbuckets().map(|bucket| bucket.num_entries()).sum::<usize>() == 1 {
+                                let _ = kad.bootstrap();
+                            }
+                            self.dial_routed(peer, addresses.into_vec());
+                        },
+                        SwarmEvent::Behaviour(PappapBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                            propagation_source, message_id, message,
+                        })) => {
+                            println!("📩 Gossip Message on {} from {:?}", message.topic, message.source);
+                            let acceptance = match NetworkMessage::decode_on(&message.data, &self.chain_id, &message.topic) {
+                                Ok(msg) => {
+                                    let inbound = InboundMessage { message: msg, id: message_id.clone(), source: propagation_source };
+                                    if self.inbound_tx.send(inbound).is_ok() {
+                                        continue; // Chain trả kết quả qua verdict_rx
+                                    }
+                                    println!("⚠️ Importer is not running, message dropped");
+                                    gossipsub::MessageAcceptance::Ignore
+                                }
+                                Err(e) => {
+                                    println!("🚫 Gossip Message Rejected: {}", e);
+                                    gossipsub::MessageAcceptance::Reject
+                                }
+                            };
+                            self.report(Verdict { id: message_id, source: propagation_source, acceptance });
+                        },
+                        SwarmEvent::Behaviour(PappapBehaviourEvent::Sync(event)) => self.on_sync_event(event),
                         _ => {}
                     }
                 }
-                // 2. Xử lý lệnh từ Chain (Broadcast Block)
-                Some(data) = self.command_rx.recv() => {
-                    if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(self.topic.clone(), data) {
-                        println!("❌ Broadcast Failed: {:?}", e);
-                    } else {
-                        println!("📡 Block Broadcasted to Network");
+                // 2. Xử lý lệnh từ Chain (Broadcast Block / Tx)
+                Some(msg) = self.command_rx.recv() => self.publish(&msg),
+                // 3. Kết quả kiểm tra message gossip từ Chain
+                Some(verdict) = self.verdict_rx.recv() => self.report(verdict),
+                // 4. Cập nhật điểm peer, gỡ ban hết hạn, kết nối lại bootstrap peer
+                _ = refresh.tick() => {
+                    self.refresh_peers();
+                    self.dial_bootstrap();
+                },
+                // 5. Random walk trên DHT để làm mới routing table và tìm peer mới
+                _ = kad_walk.tick() => {
+                    let _ = self.swarm.behaviour_mut().kad.bootstrap(); // Lỗi duy nhất: chưa biết peer nào
+                },
+                // 6. Request sync từ SyncClient
+                Some(command) = self.sync_rx.recv() => match command {
+                    SyncCommand::Request { peer, request, reply } => {
+                        let id = self.swarm.behaviour_mut().sync.send_request(&peer, request);
+                        self.sync_pending.insert(id, reply);
                     }
-                }
+                    SyncCommand::Respond { channel, response } => {
+                        // Lỗi duy nhất: peer đã đóng kết nối hoặc hết hạn chờ
+                        let _ = self.swarm.behaviour_mut().sync.send_response(channel, response);
+                    }
+                    SyncCommand::Peers(reply) => {
+                        let _ = reply.send(self.swarm.connected_peers().copied().collect());
+                    }
+                },
             }
         }
     }
 }
+
+/// Đọc khóa node từ `path`, tạo mới và lưu lại nếu chưa có. Không có path: khóa tạm, PeerId đổi mỗi lần chạy.
+pub fn load_node_key(path: Option<&Path>) -> io::Result<identity::Keypair> {
+    let Some(path) = path else {
+        return Ok(identity::Keypair::generate_ed25519());
+    };
+    let invalid = |e: identity::DecodingError| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e));
+    match fs::read(path) {
+        Ok(bytes) => return identity::Keypair::from_protobuf_encoding(&bytes).map_err(invalid),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        Err(_) => {}
+    }
+    let key = identity::Keypair::generate_ed25519();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600); // Chỉ chủ node đọc được
+    options.open(path)?.write_all(&key.to_protobuf_encoding().map_err(invalid)?)?;
+    println!("🔑 Generated node key {}", path.display());
+    Ok(key)
+}
+
+/// Tách `/p2p/<peer id>` ở cuối multiaddr (Kademlia cần biết peer id của địa chỉ)
+fn split_peer_id(addr: &Multiaddr) -> Option<(PeerId, Multiaddr)> {
+    let mut addr = addr.clone();
+    match addr.pop()? {
+        libp2p::multiaddr::Protocol::P2p(peer) => Some((peer, addr)),
+        _ => None,
+    }
+}
+
+/// Chấm điểm peer theo gossipsub v1.1: mỗi block/tx sai (bị Reject) trừ điểm theo bình phương số lần.
+/// Tắt phạt "ít message trong mesh" vì topic tx/governance/ai vốn thưa.
+fn peer_score_params(chain_id: &str) -> gossipsub::PeerScoreParams {
+    let mut params = gossipsub::PeerScoreParams::default();
+    for topic in GossipTopic::ALL {
+        let topic_params = gossipsub::TopicScoreParams {
+            topic_weight: 1.0,
+            // Ở trong mesh 1 giờ được tối đa +36 điểm; một message sai đã trừ 20
+            time_in_mesh_weight: 0.01,
+            time_in_mesh_quantum: Duration::from_secs(1),
+            mesh_message_deliveries_weight: 0.0,
+            mesh_failure_penalty_weight: 0.0,
+            invalid_message_deliveries_weight: GOSSIP_INVALID_MESSAGE_WEIGHT,
+            invalid_message_deliveries_decay: 0.99,
+            ..Default::default()
+        };
+        params.topics.insert(topic.ident(chain_id).hash(), topic_params);
+    }
+    params
+}
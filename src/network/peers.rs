@@ -0,0 +1,107 @@
+// src/network/peers.rs
+// Sổ peer: điểm gossipsub, số message sai và lệnh ban. P2PNode ghi, API đọc (GET /peers).
+use crate::constants::{PEER_BAN_SECS, PEER_MAX_STRIKES, PEER_STRIKE_WINDOW_SECS};
+use libp2p::PeerId;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PeerInfo {
+    pub peer_id: String,
+    pub connected: bool,
+    pub score: f64, // Điểm gossipsub (âm = peer xấu)
+    pub invalid_messages: u32, // Số block/tx sai trong cửa sổ hiện tại
+    pub banned_until: Option<u64>, // Unix timestamp (giây)
+}
+
+#[derive(Default)]
+struct PeerRecord {
+    connected: bool,
+    score: f64,
+    strikes: u32,
+    last_strike: u64,
+    banned_until: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct PeerBook {
+    peers: RwLock<HashMap<PeerId, PeerRecord>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+impl PeerBook {
+    pub fn set_connected(&self, peer: PeerId, connected: bool) {
+        let mut peers = self.peers.write().unwrap();
+        if connected {
+            peers.entry(peer).or_default().connected = true;
+            return;
+        }
+        // Peer sạch ngắt kết nối thì quên luôn; peer có vết thì giữ để không "rửa" được bằng cách kết nối lại
+        if let Some(record) = peers.get_mut(&peer) {
+            record.connected = false;
+            if record.strikes == 0 && record.banned_until.is_none() {
+                peers.remove(&peer);
+            }
+        }
+    }
+
+    pub fn set_score(&self, peer: &PeerId, score: f64) {
+        if let Some(record) = self.peers.write().unwrap().get_mut(peer) {
+            record.score = score;
+        }
+    }
+
+    /// Ghi nhận một block/tx sai từ `peer`. Trả về true nếu peer vừa bị ban.
+    pub fn strike(&self, peer: PeerId) -> bool {
+        let now = now();
+        let mut peers = self.peers.write().unwrap();
+        let record = peers.entry(peer).or_default();
+        if record.banned_until.is_some() {
+            return false;
+        }
+        if now.saturating_sub(record.last_strike) > PEER_STRIKE_WINDOW_SECS {
+            record.strikes = 0;
+        }
+        record.strikes += 1;
+        record.last_strike = now;
+        if record.strikes < PEER_MAX_STRIKES {
+            return false;
+        }
+        record.banned_until = Some(now + PEER_BAN_SECS);
+        true
+    }
+
+    /// Gỡ các lệnh ban đã hết hạn, trả về peer được gỡ
+    pub fn expire_bans(&self) -> Vec<PeerId> {
+        let now = now();
+        let mut peers = self.peers.write().unwrap();
+        let expired: Vec<PeerId> = peers.iter()
+            .filter(|(_, r)| r.banned_until.is_some_and(|until| until <= now))
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in &expired {
+            peers.remove(peer);
+        }
+        expired
+    }
+
+    /// Mọi peer đang kết nối hoặc có vết, điểm cao trước
+    pub fn list(&self) -> Vec<PeerInfo> {
+        let mut list: Vec<PeerInfo> = self.peers.read().unwrap().iter()
+            .map(|(peer, r)| PeerInfo {
+                peer_id: peer.to_string(),
+                connected: r.connected,
+                score: r.score,
+                invalid_messages: r.strikes,
+                banned_until: r.banned_until,
+            })
+            .collect();
+        list.sort_by(|a, b| b.score.total_cmp(&a.score));
+        list
+    }
+}
@@ -0,0 +1,509 @@
+// src/network/sync.rs
+// Giao thức đồng bộ chuỗi qua P2P: hỏi trạng thái, header (from, count) và thân block (theo hash).
+// Gossip chỉ mang block mới, nên node vào mạng muộn hoặc vừa khởi động lại
+// dùng giao thức này để tải phần chuỗi còn thiếu từ các peer.
+//
+// Chạy trên request-response của libp2p, giao thức "/pappap/sync/1", mỗi request một substream:
+// - mỗi message = độ dài u32 big-endian + bincode, tối đa SYNC_MAX_MESSAGE_BYTES (32 MiB),
+//   vượt quá thì từ chối trước khi cấp phát bộ nhớ;
+// - mỗi lượt hỏi-đáp giới hạn SYNC_REQUEST_TIMEOUT_SECS ở cả hai phía;
+// - một response chứa tối đa SYNC_MAX_HEADERS header hoặc SYNC_MAX_BLOCKS block.
+use crate::constants::{
+    SYNC_INTERVAL_SECS, SYNC_MAX_BLOCKS, SYNC_MAX_HEADERS, SYNC_MAX_MESSAGE_BYTES, SYNC_REQUEST_TIMEOUT_SECS,
+};
+use crate::core::block::{Block, BlockHeader};
+use crate::core::chain::PappapChain;
+use crate::core::chain_spec::ChainSpec;
+use crate::core::fork_choice::ChainWeight;
+use crate::core::snapshot::StateSnapshot;
+use crate::core::storage::ChainStore;
+use crate::core::validation::BlockError;
+use async_trait::async_trait;
+use libp2p::futures::{future, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response::{self, OutboundFailure, ProtocolSupport, ResponseChannel};
+use libp2p::{PeerId, StreamProtocol};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, Duration};
+
+const SYNC_PROTOCOL: StreamProtocol = StreamProtocol::new("/pappap/sync/1");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SyncRequest {
+    Status,
+    Headers { from: u64, count: u64 },
+    Blocks { hashes: Vec<String> },
+    Snapshot,
+}
+
+/// Tóm tắt chuỗi chính của một node
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChainStatus {
+    pub chain_id: String,
+    pub height: u64,
+    pub head_hash: String,
+    pub weight: ChainWeight, // Trọng lượng nhánh chính tại head_hash
+    pub pruned_below: u64, // Node không còn thân các block 1..pruned_below
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SyncResponse {
+    Status(ChainStatus),
+    Headers(Vec<BlockHeader>), // Tối đa SYNC_MAX_HEADERS header liên tiếp của chuỗi chính
+    Blocks(Vec<Block>), // Chỉ các block còn thân, tối đa SYNC_MAX_BLOCKS
+    Snapshot(Option<Box<StateSnapshot>>), // Snapshot mới nhất nếu còn nằm trên chuỗi chính
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+    Request(OutboundFailure), // Không gửi được, hết hạn chờ hoặc message hỏng
+    Closed, // Node P2P đã dừng
+    UnexpectedResponse,
+    ForkNotFound, // Không có block chung nào với peer (khác genesis)
+    BrokenHeaderChain { index: u64 },
+    MissingBlock(String), // Không peer nào trả về thân block này
+    Block { index: u64, error: BlockError },
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::Request(e) => write!(f, "sync request failed: {}", e),
+            SyncError::Closed => write!(f, "P2P node is not running"),
+            SyncError::UnexpectedResponse => write!(f, "peer answered with an unexpected response"),
+            SyncError::ForkNotFound => write!(f, "peer shares no block with the local chain"),
+            SyncError::BrokenHeaderChain { index } => write!(f, "header #{} does not link to its parent", index),
+            SyncError::MissingBlock(hash) => write!(f, "no peer served block {}", hash),
+            SyncError::Block { index, error } => write!(f, "block #{} rejected: {}", index, error),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+impl From<OutboundFailure> for SyncError {
+    fn from(e: OutboundFailure) -> Self {
+        SyncError::Request(e)
+    }
+}
+
+// --- Codec ---
+
+async fn write_message<W, T>(io: &mut W, value: &T) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+    T: Serialize,
+{
+    let bytes = bincode::serialize(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|len| *len <= SYNC_MAX_MESSAGE_BYTES)
+        .ok_or_else(|| too_large(bytes.len()))?;
+    io.write_all(&len.to_be_bytes()).await?;
+    io.write_all(&bytes).await
+}
+
+async fn read_message<R, T>(io: &mut R) -> io::Result<T>
+where
+    R: AsyncRead + Unpin + Send,
+    T: serde::de::DeserializeOwned,
+{
+    let mut len = [0u8; 4];
+    io.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len);
+    if len > SYNC_MAX_MESSAGE_BYTES {
+        return Err(too_large(len as usize));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    io.read_exact(&mut bytes).await?;
+    bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn too_large(len: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("sync message of {} bytes is too large", len))
+}
+
+/// Mã hóa SyncRequest / SyncResponse trên substream: độ dài u32 big-endian + bincode
+#[derive(Clone, Default)]
+pub struct SyncCodec;
+
+#[async_trait]
+impl request_response::Codec for SyncCodec {
+    type Protocol = StreamProtocol;
+    type Request = SyncRequest;
+    type Response = SyncResponse;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<SyncRequest>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn read_response<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<SyncResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn write_request<T>(&mut self, _: &StreamProtocol, io: &mut T, request: SyncRequest) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, &request).await
+    }
+
+    async fn write_response<T>(&mut self, _: &StreamProtocol, io: &mut T, response: SyncResponse) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, &response).await
+    }
+}
+
+pub type SyncBehaviour = request_response::Behaviour<SyncCodec>;
+pub type SyncEvent = request_response::Event<SyncRequest, SyncResponse>;
+
+pub fn new_behaviour() -> SyncBehaviour {
+    let config = request_response::Config::default()
+        .with_request_timeout(Duration::from_secs(SYNC_REQUEST_TIMEOUT_SECS));
+    SyncBehaviour::with_codec(SyncCodec, [(SYNC_PROTOCOL, ProtocolSupport::Full)], config)
+}
+
+// --- Client ---
+
+/// Request của peer, chờ `serve_sync` trả lời qua `channel`
+pub struct InboundSync {
+    pub peer: PeerId,
+    pub request: SyncRequest,
+    pub channel: ResponseChannel<SyncResponse>,
+}
+
+/// Lệnh gửi vào vòng lặp của P2PNode
+pub enum SyncCommand {
+    Request { peer: PeerId, request: SyncRequest, reply: oneshot::Sender<Result<SyncResponse, SyncError>> },
+    Respond { channel: ResponseChannel<SyncResponse>, response: SyncResponse },
+    Peers(oneshot::Sender<Vec<PeerId>>),
+}
+
+/// Gửi request sync tới peer thông qua P2PNode
+#[derive(Clone)]
+pub struct SyncClient {
+    commands: mpsc::UnboundedSender<SyncCommand>,
+}
+
+impl SyncClient {
+    pub fn new(commands: mpsc::UnboundedSender<SyncCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Các peer đang kết nối
+    pub async fn peers(&self) -> Vec<PeerId> {
+        let (reply, peers) = oneshot::channel();
+        if self.commands.send(SyncCommand::Peers(reply)).is_err() {
+            return Vec::new();
+        }
+        peers.await.unwrap_or_default()
+    }
+
+    /// Hết hạn chờ do request-response lo (SYNC_REQUEST_TIMEOUT_SECS)
+    pub async fn request(&self, peer: PeerId, request: SyncRequest) -> Result<SyncResponse, SyncError> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(SyncCommand::Request { peer, request, reply }).map_err(|_| SyncError::Closed)?;
+        response.await.map_err(|_| SyncError::Closed)?
+    }
+
+    pub async fn status(&self, peer: PeerId) -> Result<ChainStatus, SyncError> {
+        match self.request(peer, SyncRequest::Status).await? {
+            SyncResponse::Status(status) => Ok(status),
+            _ => Err(SyncError::UnexpectedResponse),
+        }
+    }
+
+    pub async fn headers(&self, peer: PeerId, from: u64, count: u64) -> Result<Vec<BlockHeader>, SyncError> {
+        match self.request(peer, SyncRequest::Headers { from, count }).await? {
+            SyncResponse::Headers(headers) if headers.len() as u64 <= count => Ok(headers),
+            _ => Err(SyncError::UnexpectedResponse),
+        }
+    }
+
+    pub async fn blocks(&self, peer: PeerId, hashes: Vec<String>) -> Result<Vec<Block>, SyncError> {
+        match self.request(peer, SyncRequest::Blocks { hashes }).await? {
+            SyncResponse::Blocks(blocks) => Ok(blocks),
+            _ => Err(SyncError::UnexpectedResponse),
+        }
+    }
+
+    pub async fn snapshot(&self, peer: PeerId) -> Result<Option<StateSnapshot>, SyncError> {
+        match self.request(peer, SyncRequest::Snapshot).await? {
+            SyncResponse::Snapshot(snapshot) => Ok(snapshot.map(|s| *s)),
+            _ => Err(SyncError::UnexpectedResponse),
+        }
+    }
+
+    fn respond(&self, channel: ResponseChannel<SyncResponse>, response: SyncResponse) -> Result<(), SyncError> {
+        self.commands.send(SyncCommand::Respond { channel, response }).map_err(|_| SyncError::Closed)
+    }
+}
+
+// --- Server ---
+
+pub fn local_status<S: ChainStore>(storage: &S, spec: &ChainSpec) -> ChainStatus {
+    ChainStatus {
+        chain_id: spec.chain_id.clone(),
+        height: storage.get_height(),
+        head_hash: storage.get_last_hash(),
+        weight: storage.get_weight(&storage.get_last_hash()).unwrap_or_default(),
+        pruned_below: storage.get_pruned_height(),
+    }
+}
+
+fn answer<S: ChainStore>(storage: &S, spec: &ChainSpec, request: SyncRequest) -> SyncResponse {
+    match request {
+        SyncRequest::Status => SyncResponse::Status(local_status(storage, spec)),
+        SyncRequest::Headers { from, count } => {
+            SyncResponse::Headers(storage.get_headers(from, count.min(SYNC_MAX_HEADERS)))
+        }
+        // Block chưa biết hoặc đã prune được bỏ qua, bên hỏi lấy từ peer khác
+        SyncRequest::Blocks { hashes } => SyncResponse::Blocks(
+            hashes.iter()
+                .take(SYNC_MAX_BLOCKS)
+                .filter_map(|hash| storage.get_block_by_hash(hash))
+                .collect(),
+        ),
+        // Snapshot của nhánh đã bị reorg khỏi chuỗi chính thì không phục vụ nữa
+        SyncRequest::Snapshot => SyncResponse::Snapshot(
+            storage.get_snapshot()
+                .filter(|s| storage.get_canonical_hash(s.height()).as_deref() == Some(s.block.hash.as_str()))
+                .map(Box::new),
+        ),
+    }
+}
+
+/// Trả lời các request sync của peer từ dữ liệu local
+pub async fn serve_sync<S: ChainStore>(
+    storage: Arc<S>,
+    spec: Arc<ChainSpec>,
+    client: SyncClient,
+    mut inbound: mpsc::UnboundedReceiver<InboundSync>,
+) {
+    println!("📚 SYNC SERVER STARTED ({})", SYNC_PROTOCOL);
+    while let Some(InboundSync { peer, request, channel }) = inbound.recv().await {
+        if client.respond(channel, answer(&*storage, &spec, request)).is_err() {
+            println!("⚠️ P2P node stopped, sync request from {} dropped", peer);
+            return;
+        }
+    }
+}
+
+// --- Sync driver ---
+
+/// Vòng lặp đồng bộ: định kỳ hỏi trạng thái các peer và tải phần chuỗi còn thiếu
+/// từ các peer đi trước. Block tải về đi qua cùng pipeline kiểm tra với block nhận từ gossip.
+pub async fn run_sync<S: ChainStore>(chain: Arc<PappapChain<S>>, client: SyncClient) {
+    println!("🔄 CHAIN SYNC STARTED");
+    loop {
+        match sync_round(&chain, &client).await {
+            Ok(0) => {}
+            // Có thể còn thiếu: tải tiếp ngay
+            Ok(imported) => {
+                println!("🔄 SYNC: imported {} blocks, head is #{}", imported, chain.storage.get_height());
+                continue;
+            }
+            Err(e) => println!("⚠️ Sync round failed: {}", e),
+        }
+        sleep(Duration::from_secs(SYNC_INTERVAL_SECS)).await;
+    }
+}
+
+/// Một lượt đồng bộ, trả về số block đã import
+async fn sync_round<S: ChainStore>(chain: &PappapChain<S>, client: &SyncClient) -> Result<u64, SyncError> {
+    let storage = &*chain.storage;
+
+    // 1. Peer cùng chain id có đỉnh cao hơn và chưa biết
+    let peers = client.peers().await;
+    let statuses = future::join_all(peers.iter().map(|peer| client.status(*peer))).await;
+    let height = storage.get_height();
+    let mut ahead: Vec<(PeerId, ChainStatus)> = peers.into_iter()
+        .zip(statuses)
+        .filter_map(|(peer, status)| Some((peer, status.ok()?)))
+        .filter(|(_, s)| s.chain_id == chain.spec.chain_id && s.height > height && !storage.has_block(&s.head_hash))
+        .collect();
+    if ahead.is_empty() {
+        return Ok(0);
+    }
+    ahead.sort_by_key(|(_, s)| std::cmp::Reverse(s.height));
+
+    // 2. Header của đoạn còn thiếu, lấy từ peer cao nhất
+    let (best, best_status) = &ahead[0];
+    let headers = missing_headers(storage, client, *best).await?;
+    let (Some(first), Some(last)) = (headers.first(), headers.last()) else { return Ok(0) };
+    println!("🔄 SYNC: fetching blocks #{}..#{} from {} peer(s), best head #{}",
+        first.index, last.index, ahead.len(), best_status.height);
+
+    // 3. Thân block, chia đoạn cho nhiều peer
+    let mut blocks = download_blocks(client, &ahead, &headers).await;
+
+    // 4. Áp dụng theo thứ tự
+    let mut imported = 0;
+    for header in &headers {
+        let hash = header.hash();
+        let block = blocks.remove(&hash).ok_or(SyncError::MissingBlock(hash))?;
+        match chain.import_block(block).await {
+            Ok(()) => imported += 1,
+            Err(BlockError::AlreadyKnown(_)) => {} // Gossip đã đưa block vào trước
+            Err(error) => return Err(SyncError::Block { index: header.index, error }),
+        }
+    }
+    Ok(imported)
+}
+
+/// Header các block chưa biết, bắt đầu ngay sau block chung cuối cùng với `peer`.
+/// Peer ở nhánh khác thì lùi dần (1, 2, 4, ... block) tới khi gặp block cha đã biết.
+async fn missing_headers<S: ChainStore>(storage: &S, client: &SyncClient, peer: PeerId) -> Result<Vec<BlockHeader>, SyncError> {
+    let mut from = storage.get_height() + 1;
+    let mut step = 1;
+    loop {
+        let headers = client.headers(peer, from, SYNC_MAX_HEADERS).await?;
+        let Some(first) = headers.first() else { return Ok(Vec::new()) };
+        if first.index != from {
+            return Err(SyncError::UnexpectedResponse);
+        }
+        if storage.get_header(&first.prev_hash).is_none() {
+            if from == 1 {
+                return Err(SyncError::ForkNotFound);
+            }
+            from = from.saturating_sub(step).max(1);
+            step *= 2;
+            continue;
+        }
+        for pair in headers.windows(2) {
+            if pair[1].index != pair[0].index + 1 || pair[1].prev_hash != pair[0].hash() {
+                return Err(SyncError::BrokenHeaderChain { index: pair[1].index });
+            }
+        }
+
+        let full = headers.len() as u64 == SYNC_MAX_HEADERS;
+        let missing: Vec<BlockHeader> = headers.into_iter()
+            .filter(|h| storage.get_header(&h.hash()).is_none())
+            .collect();
+        // Cả đoạn đã biết (vd. block nhánh phụ đã lưu): xem đoạn kế tiếp
+        if missing.is_empty() && full {
+            from += SYNC_MAX_HEADERS;
+            continue;
+        }
+        return Ok(missing);
+    }
+}
+
+/// Tải thân block của `headers` theo đoạn SYNC_MAX_BLOCKS, các đoạn chạy song song trên các peer
+/// đã có (và chưa prune) đoạn đó. Block thiếu được hỏi lại peer cao nhất một lần.
+/// Chỉ giữ block khớp đúng header đã xin.
+async fn download_blocks(
+    client: &SyncClient,
+    peers: &[(PeerId, ChainStatus)],
+    headers: &[BlockHeader],
+) -> HashMap<String, Block> {
+    let wanted: HashMap<String, &BlockHeader> = headers.iter().map(|h| (h.hash(), h)).collect();
+    let accept = |blocks: &mut HashMap<String, Block>, found: Vec<Block>| {
+        for block in found {
+            if wanted.get(&block.hash).is_some_and(|h| **h == block.header) {
+                blocks.insert(block.hash.clone(), block);
+            }
+        }
+    };
+
+    let requests = headers.chunks(SYNC_MAX_BLOCKS).enumerate().map(|(i, chunk)| {
+        let (first, last) = (chunk[0].index, chunk[chunk.len() - 1].index);
+        let eligible: Vec<PeerId> = peers.iter()
+            .filter(|(_, s)| s.height >= last && s.pruned_below <= first)
+            .map(|(peer, _)| *peer)
+            .collect();
+        let peer = eligible.get(i % eligible.len().max(1)).copied().unwrap_or(peers[0].0);
+        let hashes = chunk.iter().map(|h| h.hash()).collect();
+        client.blocks(peer, hashes)
+    });
+    let mut blocks = HashMap::new();
+    for found in future::join_all(requests).await.into_iter().flatten() {
+        accept(&mut blocks, found);
+    }
+
+    let missing: Vec<String> = headers.iter()
+        .map(|h| h.hash())
+        .filter(|hash| !blocks.contains_key(hash))
+        .collect();
+    for chunk in missing.chunks(SYNC_MAX_BLOCKS) {
+        if let Ok(found) = client.blocks(peers[0].0, chunk.to_vec()).await {
+            accept(&mut blocks, found);
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::core::chain::tests::{test_node, MINER_A, MINER_B};
+    use crate::core::memory_store::MemoryStore;
+    use crate::core::transaction::tests::{test_key, test_spec};
+
+    /// Mạng giả cho SyncClient: mỗi peer trả lời thẳng từ storage của một node, không qua P2P
+    pub(crate) fn fake_network(spec: Arc<ChainSpec>, nodes: Vec<Arc<MemoryStore>>) -> (SyncClient, Vec<PeerId>) {
+        let peers: HashMap<PeerId, Arc<MemoryStore>> = nodes.into_iter().map(|n| (PeerId::random(), n)).collect();
+        let ids: Vec<PeerId> = peers.keys().copied().collect();
+        let (commands, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                match command {
+                    SyncCommand::Request { peer, request, reply } => {
+                        let _ = reply.send(Ok(answer(&*peers[&peer], &spec, request)));
+                    }
+                    SyncCommand::Peers(reply) => {
+                        let _ = reply.send(peers.keys().copied().collect());
+                    }
+                    SyncCommand::Respond { .. } => {}
+                }
+            }
+        });
+        (SyncClient::new(commands), ids)
+    }
+
+    #[tokio::test]
+    async fn missing_headers_backs_off_to_the_fork_point() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let (local, peer) = (test_node(&spec, MINER_A).await, test_node(&spec, MINER_B).await);
+
+        // Chung block 1, sau đó local đào a2, peer đào b2..b4
+        let common = local.mine_block(true).await.unwrap();
+        peer.import_block(common.clone()).await.unwrap();
+        local.mine_block(true).await.unwrap();
+        let mut fork = Vec::new();
+        for _ in 0..3 {
+            fork.push(peer.mine_block(true).await.unwrap().hash);
+        }
+
+        let (client, ids) = fake_network(spec.clone(), vec![peer.storage.clone()]);
+        let headers = missing_headers(&*local.storage, &client, ids[0]).await.unwrap();
+        assert_eq!(headers.iter().map(|h| h.hash()).collect::<Vec<_>>(), fork);
+        assert_eq!(headers[0].prev_hash, common.hash);
+    }
+
+    #[tokio::test]
+    async fn missing_headers_rejects_peer_with_another_genesis() {
+        let spec = Arc::new(test_spec(&[&test_key(1)], 1_000));
+        let other_spec = Arc::new(test_spec(&[&test_key(2)], 1_000));
+        let local = test_node(&spec, MINER_A).await;
+        let stranger = test_node(&other_spec, MINER_B).await;
+        stranger.mine_block(true).await.unwrap();
+        stranger.mine_block(true).await.unwrap();
+
+        let (client, ids) = fake_network(other_spec, vec![stranger.storage.clone()]);
+        let result = missing_headers(&*local.storage, &client, ids[0]).await;
+        assert!(matches!(result, Err(SyncError::ForkNotFound)));
+    }
+}